@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS questions_score_idx;
+DROP TABLE IF EXISTS answer_votes;
+DROP TABLE IF EXISTS question_votes;
+ALTER TABLE answers
+    DROP COLUMN score;
+ALTER TABLE questions
+    DROP COLUMN score;
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS comments
+(
+    id          serial PRIMARY KEY,
+    content     TEXT      NOT NULL,
+    created_on  TIMESTAMP NOT NULL DEFAULT NOW(),
+    question_id integer REFERENCES questions ON DELETE CASCADE,
+    answer_id   integer REFERENCES answers ON DELETE CASCADE,
+    account_id  integer   NOT NULL,
+    -- A comment belongs to exactly one question or one answer
+    CHECK ((question_id IS NULL) <> (answer_id IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS comments_question_id_idx ON comments (question_id);
+CREATE INDEX IF NOT EXISTS comments_answer_id_idx ON comments (answer_id);
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS questions_status_idx;
+ALTER TABLE questions
+    DROP COLUMN accepted_answer,
+    DROP COLUMN status;
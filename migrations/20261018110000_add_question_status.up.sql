@@ -0,0 +1,11 @@
+ALTER TABLE questions
+    ADD COLUMN status          VARCHAR(16) NOT NULL DEFAULT 'open'
+        CHECK (status IN ('open', 'answered', 'resolved', 'closed')),
+    ADD COLUMN accepted_answer integer REFERENCES answers ON DELETE SET NULL;
+
+-- Questions answered before this migration
+UPDATE questions
+SET status = 'answered'
+WHERE EXISTS (SELECT 1 FROM answers WHERE answers.corresponding_question = questions.id);
+
+CREATE INDEX IF NOT EXISTS questions_status_idx ON questions (status);
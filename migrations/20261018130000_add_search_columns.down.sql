@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS answers_search_idx;
+DROP INDEX IF EXISTS questions_search_idx;
+ALTER TABLE answers
+    DROP COLUMN search;
+ALTER TABLE questions
+    DROP COLUMN search;
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS answers_corresponding_question_idx;
+DROP INDEX IF EXISTS questions_account_id_idx;
+DROP INDEX IF EXISTS questions_created_on_idx;
@@ -0,0 +1,3 @@
+CREATE INDEX IF NOT EXISTS questions_created_on_idx ON questions (created_on, id);
+CREATE INDEX IF NOT EXISTS questions_account_id_idx ON questions (account_id);
+CREATE INDEX IF NOT EXISTS answers_corresponding_question_idx ON answers (corresponding_question, created_on);
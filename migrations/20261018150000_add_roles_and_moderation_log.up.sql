@@ -0,0 +1,15 @@
+ALTER TABLE accounts
+    ADD COLUMN IF NOT EXISTS role VARCHAR(16) NOT NULL DEFAULT 'user'
+        CHECK (role IN ('user', 'agent', 'moderator', 'admin'));
+
+-- Kept without foreign keys, so entries outlive the content they refer to
+CREATE TABLE IF NOT EXISTS moderation_log
+(
+    id           serial PRIMARY KEY,
+    moderator_id integer     NOT NULL,
+    action       VARCHAR(32) NOT NULL
+        CHECK (action IN ('update_question', 'delete_question', 'update_answer', 'delete_answer')),
+    target_id    integer     NOT NULL,
+    author_id    integer,
+    created_on   TIMESTAMP   NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS refresh_tokens
+(
+    id         serial PRIMARY KEY,
+    account_id integer     NOT NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_on TIMESTAMP   NOT NULL DEFAULT NOW(),
+    expires_on TIMESTAMP   NOT NULL,
+    revoked_on TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_account_id_idx ON refresh_tokens (account_id);
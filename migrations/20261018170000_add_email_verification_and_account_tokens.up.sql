@@ -0,0 +1,16 @@
+-- Accounts from before verification existed keep working
+ALTER TABLE accounts ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE accounts SET email_verified = TRUE;
+
+CREATE TABLE IF NOT EXISTS account_tokens
+(
+    id         serial PRIMARY KEY,
+    account_id integer     NOT NULL,
+    purpose    VARCHAR(32) NOT NULL CHECK (purpose IN ('verify_email', 'reset_password')),
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_on TIMESTAMP   NOT NULL DEFAULT NOW(),
+    expires_on TIMESTAMP   NOT NULL,
+    used_on    TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS account_tokens_account_id_idx ON account_tokens (account_id);
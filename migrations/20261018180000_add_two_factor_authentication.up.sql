@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS account_totp
+(
+    account_id     integer PRIMARY KEY,
+    secret         VARCHAR(64) NOT NULL,
+    created_on     TIMESTAMP   NOT NULL DEFAULT NOW(),
+    enabled_on     TIMESTAMP,
+    last_used_step BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS recovery_codes
+(
+    id         serial PRIMARY KEY,
+    account_id integer     NOT NULL,
+    code_hash  VARCHAR(64) NOT NULL,
+    used_on    TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS recovery_codes_account_id_idx ON recovery_codes (account_id);
+
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS mfa BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE account_tokens DROP CONSTRAINT IF EXISTS account_tokens_purpose_check;
+ALTER TABLE account_tokens ADD CONSTRAINT account_tokens_purpose_check
+    CHECK (purpose IN ('verify_email', 'reset_password', 'login_challenge'));
@@ -0,0 +1,8 @@
+-- Keys are `email:<address>` or `ip:<address>`
+CREATE TABLE IF NOT EXISTS login_failures
+(
+    key             VARCHAR(320) PRIMARY KEY,
+    failures        integer      NOT NULL DEFAULT 0,
+    last_failure_on TIMESTAMP    NOT NULL,
+    locked_until    TIMESTAMP
+);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS api_keys
+(
+    id           serial PRIMARY KEY,
+    account_id   integer      NOT NULL,
+    name         VARCHAR(255) NOT NULL,
+    hint         VARCHAR(16)  NOT NULL,
+    key_hash     VARCHAR(64)  NOT NULL UNIQUE,
+    scopes       TEXT[]       NOT NULL,
+    created_on   TIMESTAMP    NOT NULL DEFAULT NOW(),
+    expires_on   TIMESTAMP,
+    last_used_on TIMESTAMP,
+    revoked_on   TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS api_keys_account_id_idx ON api_keys (account_id);
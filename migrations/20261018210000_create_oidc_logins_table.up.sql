@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS oidc_logins
+(
+    state_hash    VARCHAR(64)  PRIMARY KEY,
+    code_verifier VARCHAR(128) NOT NULL,
+    nonce         VARCHAR(64)  NOT NULL,
+    expires_on    TIMESTAMP    NOT NULL
+);
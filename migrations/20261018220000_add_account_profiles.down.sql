@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS answers_account_id_idx;
+DROP INDEX IF EXISTS accounts_id_idx;
+
+ALTER TABLE accounts
+    DROP COLUMN IF EXISTS created_on,
+    DROP COLUMN IF EXISTS bio,
+    DROP COLUMN IF EXISTS avatar_url,
+    DROP COLUMN IF EXISTS display_name;
@@ -0,0 +1,9 @@
+ALTER TABLE accounts
+    ADD COLUMN IF NOT EXISTS display_name VARCHAR(64),
+    ADD COLUMN IF NOT EXISTS avatar_url   VARCHAR(2048),
+    ADD COLUMN IF NOT EXISTS bio          TEXT,
+    -- Accounts from before profiles existed show the day of the migration
+    ADD COLUMN IF NOT EXISTS created_on   TIMESTAMP NOT NULL DEFAULT NOW();
+
+CREATE INDEX IF NOT EXISTS accounts_id_idx ON accounts (id);
+CREATE INDEX IF NOT EXISTS answers_account_id_idx ON answers (account_id);
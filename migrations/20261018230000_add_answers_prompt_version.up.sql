@@ -0,0 +1,2 @@
+-- Template version that generated an answer, NULL for answers people wrote
+ALTER TABLE answers ADD COLUMN IF NOT EXISTS prompt_version VARCHAR(64);
@@ -1,12 +1,45 @@
+use std::collections::HashMap;
 use std::env;
+use std::sync::Arc;
+use std::time::Duration;
 
 use clap::{Parser, ValueEnum};
 use dotenv::dotenv;
 
 use crate::errors::Error;
+use crate::services::ai_provider::{FakeAiProvider, SharedAiProvider};
+use crate::services::google_ai_service::GeminiProvider;
+use crate::services::ollama_service::OllamaProvider;
+use crate::services::openai_service::OpenAiProvider;
+use crate::services::mailer::{parse_mailbox, AccountMailer, FileMailer, SharedMailer, SmtpMailer, SmtpSecurity};
+use crate::services::oidc::OidcClient;
+use crate::types::account::AdminEmails;
+use crate::types::key_ring::KeyRing;
+use crate::types::prompt::PromptTemplate;
 
+pub const AI_PROVIDER: &str = "AI_PROVIDER";
 pub const GOOGLE_AI_KEY: &str = "GOOGLE_AI_KEY";
+pub const GEMINI_BASE_URL: &str = "GEMINI_BASE_URL";
+pub const GEMINI_MODEL: &str = "GEMINI_MODEL";
+pub const GEMINI_TIMEOUT_SECS: &str = "GEMINI_TIMEOUT_SECS";
+pub const OPENAI_BASE_URL: &str = "OPENAI_BASE_URL";
+pub const OPENAI_MODEL: &str = "OPENAI_MODEL";
+pub const OPENAI_API_KEY: &str = "OPENAI_API_KEY";
+pub const OPENAI_TIMEOUT_SECS: &str = "OPENAI_TIMEOUT_SECS";
+pub const OLLAMA_BASE_URL: &str = "OLLAMA_BASE_URL";
+pub const OLLAMA_MODEL: &str = "OLLAMA_MODEL";
+pub const OLLAMA_TIMEOUT_SECS: &str = "OLLAMA_TIMEOUT_SECS";
+/// JSON file with the prompt template, the built-in one is used without it
+pub const PROMPT_TEMPLATE_FILE: &str = "PROMPT_TEMPLATE_FILE";
 pub const PASETO_KEY: &str = "PASETO_KEY";
+pub const PASETO_KEY_ID: &str = "PASETO_KEY_ID";
+/// Keys that only verify tokens, as `id:key` pairs separated by commas
+pub const PASETO_PREVIOUS_KEYS: &str = "PASETO_PREVIOUS_KEYS";
+pub const PASETO_KEY_FILE: &str = "PASETO_KEY_FILE";
+/// `k4.secret` PASERK, switches new tokens to `v4.public`
+pub const PASETO_SECRET_KEY: &str = "PASETO_SECRET_KEY";
+/// `k4.public` PASERKs of retired signing keys, separated by commas
+pub const PASETO_PREVIOUS_PUBLIC_KEYS: &str = "PASETO_PREVIOUS_PUBLIC_KEYS";
 pub const PORT: &str = "PORT";
 pub const POSTGRES_USER: &str = "POSTGRES_USER";
 pub const POSTGRES_PASSWORD: &str = "POSTGRES_PASSWORD";
@@ -15,6 +48,30 @@ pub const POSTGRES_PORT: &str = "POSTGRES_PORT";
 pub const POSTGRES_DB: &str = "POSTGRES_DB";
 
 pub const DB_TYPE: &str = "DB_TYPE";
+pub const PAGE_SIZE: &str = "PAGE_SIZE";
+pub const MAX_PAGE_SIZE: &str = "MAX_PAGE_SIZE";
+
+pub const APP_URL: &str = "APP_URL";
+pub const MAIL_TRANSPORT: &str = "MAIL_TRANSPORT";
+pub const MAIL_FROM: &str = "MAIL_FROM";
+pub const MAIL_FILE: &str = "MAIL_FILE";
+pub const SMTP_HOST: &str = "SMTP_HOST";
+pub const SMTP_PORT: &str = "SMTP_PORT";
+pub const SMTP_SECURITY: &str = "SMTP_SECURITY";
+pub const SMTP_USERNAME: &str = "SMTP_USERNAME";
+pub const SMTP_PASSWORD: &str = "SMTP_PASSWORD";
+/// Single sign-on is off while the issuer is empty
+pub const OIDC_ISSUER_URL: &str = "OIDC_ISSUER_URL";
+pub const OIDC_CLIENT_ID: &str = "OIDC_CLIENT_ID";
+pub const OIDC_CLIENT_SECRET: &str = "OIDC_CLIENT_SECRET";
+pub const OIDC_REDIRECT_URL: &str = "OIDC_REDIRECT_URL";
+/// Whether ID tokens without the `email_verified` claim may create accounts
+pub const OIDC_PROVISION_UNVERIFIED: &str = "OIDC_PROVISION_UNVERIFIED";
+/// Emails separated by commas that become admins on their first verified login
+pub const ADMIN_EMAILS: &str = "ADMIN_EMAILS";
+/// Whether `X-Forwarded-For` names the client, only true behind a single reverse
+/// proxy that appends the address it sees, its rightmost entry is used
+pub const TRUST_PROXY: &str = "TRUST_PROXY";
 
 #[derive(ValueEnum, Debug, Clone)] // ArgEnum here
 #[clap(rename_all = "kebab_case")]
@@ -23,6 +80,27 @@ pub enum DatabaseType {
     Memory,
 }
 
+#[derive(ValueEnum, Debug, Clone)]
+#[clap(rename_all = "kebab_case")]
+pub enum AiProviderType {
+    Gemini,
+    /// Any OpenAI-compatible chat completions endpoint
+    Openai,
+    /// A local or self-hosted Ollama server
+    Ollama,
+    /// Canned answers without any model, for tests and local development
+    Fake,
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+#[clap(rename_all = "kebab_case")]
+pub enum MailTransport {
+    Smtp,
+    /// Appends mails to `MAIL_FILE`
+    File,
+    Stdout,
+}
+
 /// Q&A web service API
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -51,6 +129,107 @@ pub struct Config {
     /// Database type
     #[clap(long, value_enum, default_value = "postgres")]
     pub db_type: DatabaseType,
+    /// Number of items in a page when the client doesn't ask for a size
+    #[clap(long, default_value = "20")]
+    pub page_size: i64,
+    /// Largest page size a client may ask for
+    #[clap(long, default_value = "100")]
+    pub max_page_size: i64,
+    /// JSON file with all token keys, used instead of `PASETO_KEY`
+    #[clap(long)]
+    pub paseto_key_file: Option<String>,
+    /// Base URL of the web app, links in mails point there
+    #[clap(long, default_value = "http://localhost:3000")]
+    pub app_url: String,
+    /// How mails are delivered
+    #[clap(long, value_enum, default_value = "stdout")]
+    pub mail_transport: MailTransport,
+    /// Sender of all mails
+    #[clap(long, default_value = "rush <no-reply@localhost>")]
+    pub mail_from: String,
+    /// File the `file` transport appends mails to
+    #[clap(long, default_value = "mail.log")]
+    pub mail_file: String,
+    /// SMTP server for the `smtp` transport
+    #[clap(long, default_value = "localhost")]
+    pub smtp_host: String,
+    /// PORT of the SMTP server
+    #[clap(long, default_value = "587")]
+    pub smtp_port: u16,
+    /// Use `none` for a local SMTP catcher
+    #[clap(long, value_enum, default_value = "starttls")]
+    pub smtp_security: SmtpSecurity,
+    /// SMTP user, mails are sent without authentication when empty
+    #[clap(long, default_value = "")]
+    pub smtp_username: String,
+    /// SMTP password
+    #[clap(long, default_value = "")]
+    pub smtp_password: String,
+    /// Model behind the generated answers. Without it Gemini is used when
+    /// `GOOGLE_AI_KEY` is set, otherwise AI answers are off.
+    #[clap(long, value_enum)]
+    pub ai_provider: Option<AiProviderType>,
+    /// Generative Language API, or a proxy in front of it
+    #[clap(long, default_value = "https://generativelanguage.googleapis.com/v1beta")]
+    pub gemini_base_url: String,
+    /// Gemini model answering the questions, it has to support system instructions.
+    /// Replaces the `gemini-pro` that was hardcoded before, which doesn't.
+    #[clap(long, default_value = "gemini-1.5-flash")]
+    pub gemini_model: String,
+    /// Seconds to wait for Gemini, per attempt
+    #[clap(long, default_value = "30")]
+    pub gemini_timeout_secs: u64,
+    /// API key for Gemini, read from `GOOGLE_AI_KEY`
+    #[clap(skip)]
+    pub google_ai_key: String,
+    /// OpenAI-compatible API, e.g. `http://vllm.internal:8000/v1`
+    #[clap(long, default_value = "https://api.openai.com/v1")]
+    pub openai_base_url: String,
+    /// Model name the endpoint serves
+    #[clap(long, default_value = "gpt-4o-mini")]
+    pub openai_model: String,
+    /// Sent as bearer token, left empty for endpoints without authentication
+    #[clap(long, default_value = "")]
+    pub openai_api_key: String,
+    /// Seconds to wait for the OpenAI-compatible endpoint, per attempt
+    #[clap(long, default_value = "60")]
+    pub openai_timeout_secs: u64,
+    /// Ollama server
+    #[clap(long, default_value = "http://localhost:11434")]
+    pub ollama_base_url: String,
+    /// Model pulled into Ollama
+    #[clap(long, default_value = "llama3")]
+    pub ollama_model: String,
+    /// Seconds to wait for Ollama per attempt, local models on a CPU are slow
+    #[clap(long, default_value = "120")]
+    pub ollama_timeout_secs: u64,
+    /// JSON file with the prompt template for generated answers, see `PromptTemplate`
+    #[clap(long)]
+    pub prompt_template_file: Option<String>,
+    /// Accounts promoted to admin when they log in, separated by commas
+    #[clap(long, default_value = "")]
+    pub admin_emails: String,
+    /// Take the client address from the rightmost `X-Forwarded-For` entry, for
+    /// lockouts by IP. Only for exactly one reverse proxy in front of rush that
+    /// appends the address it sees, entries left of it are made up by clients.
+    #[clap(long)]
+    pub trust_proxy: bool,
+    /// OpenID Connect provider for single sign-on, e.g. `https://login.example.com/realms/staff`
+    #[clap(long, default_value = "")]
+    pub oidc_issuer_url: String,
+    /// Client registered for rush at the provider
+    #[clap(long, default_value = "")]
+    pub oidc_client_id: String,
+    /// Left empty for a public client
+    #[clap(long, default_value = "")]
+    pub oidc_client_secret: String,
+    /// Where the provider sends the browser back to, `/oidc/callback` of this service
+    #[clap(long, default_value = "http://localhost:8080/oidc/callback")]
+    pub oidc_redirect_url: String,
+    /// Create accounts for ID tokens that leave out `email_verified`, for providers
+    /// that only hand out addresses they checked. Never links existing accounts.
+    #[clap(long)]
+    pub oidc_provision_unverified: bool,
 }
 
 impl Config {
@@ -58,11 +237,23 @@ impl Config {
         dotenv().ok();
         let config = Config::parse();
 
-        if env::var(GOOGLE_AI_KEY).is_err() {
-            panic!("Google_AI_KEY not set");
-        }
+        let google_ai_key = env::var(GOOGLE_AI_KEY).unwrap_or_default();
+        let ai_provider = match env::var(AI_PROVIDER) {
+            // Falling back to another provider could send questions where they mustn't go
+            Ok(str) if !str.is_empty() => Some(AiProviderType::from_str(&str, false).map_err(|_| {
+                Error::AiConfigError(format!("{}={} is none of gemini, openai, ollama or fake", AI_PROVIDER, str))
+            })?),
+            _ => None,
+        };
+        let ai_provider = ai_provider
+            .or(config.ai_provider.to_owned())
+            .or(Some(AiProviderType::Gemini).filter(|_| !google_ai_key.is_empty()));
 
-        if env::var(PASETO_KEY).is_err() {
+        let paseto_key_file = env::var(PASETO_KEY_FILE)
+            .ok()
+            .filter(|path| !path.is_empty())
+            .or(config.paseto_key_file);
+        if env::var(PASETO_KEY).is_err() && paseto_key_file.is_none() {
             panic!("PASETO_KEY not set");
         }
 
@@ -86,6 +277,19 @@ impl Config {
         };
 
 
+        let page_size = env::var(PAGE_SIZE).unwrap_or(config.page_size.to_string());
+        let max_page_size = env::var(MAX_PAGE_SIZE).unwrap_or(config.max_page_size.to_string());
+
+        let mail_transport = match env::var(MAIL_TRANSPORT) {
+            Ok(str) => MailTransport::from_str(&str, false).unwrap_or(config.mail_transport.to_owned()),
+            Err(_) => config.mail_transport.to_owned(),
+        };
+        let smtp_security = match env::var(SMTP_SECURITY) {
+            Ok(str) => SmtpSecurity::from_str(&str, false).unwrap_or(config.smtp_security),
+            Err(_) => config.smtp_security,
+        };
+        let smtp_port = env_or(SMTP_PORT, config.smtp_port.to_string());
+
         Ok(Config {
             log_level: config.log_level,
             port,
@@ -95,6 +299,164 @@ impl Config {
             db_port: db_port.parse::<u16>().map_err(Error::ParseError)?,
             db_name,
             db_type,
+            page_size: page_size.parse::<i64>().map_err(Error::ParseError)?,
+            max_page_size: max_page_size.parse::<i64>().map_err(Error::ParseError)?,
+            paseto_key_file,
+            app_url: env_or(APP_URL, config.app_url),
+            mail_transport,
+            mail_from: env_or(MAIL_FROM, config.mail_from),
+            mail_file: env_or(MAIL_FILE, config.mail_file),
+            smtp_host: env_or(SMTP_HOST, config.smtp_host),
+            smtp_port: smtp_port.parse::<u16>().map_err(Error::ParseError)?,
+            smtp_security,
+            smtp_username: env_or(SMTP_USERNAME, config.smtp_username),
+            smtp_password: env_or(SMTP_PASSWORD, config.smtp_password),
+            ai_provider,
+            gemini_base_url: env_or(GEMINI_BASE_URL, config.gemini_base_url),
+            gemini_model: env_or(GEMINI_MODEL, config.gemini_model),
+            gemini_timeout_secs: env_or(GEMINI_TIMEOUT_SECS, config.gemini_timeout_secs.to_string())
+                .parse::<u64>()
+                .map_err(Error::ParseError)?,
+            google_ai_key,
+            openai_base_url: env_or(OPENAI_BASE_URL, config.openai_base_url),
+            openai_model: env_or(OPENAI_MODEL, config.openai_model),
+            openai_api_key: env_or(OPENAI_API_KEY, config.openai_api_key),
+            openai_timeout_secs: env_or(OPENAI_TIMEOUT_SECS, config.openai_timeout_secs.to_string())
+                .parse::<u64>()
+                .map_err(Error::ParseError)?,
+            ollama_base_url: env_or(OLLAMA_BASE_URL, config.ollama_base_url),
+            ollama_model: env_or(OLLAMA_MODEL, config.ollama_model),
+            ollama_timeout_secs: env_or(OLLAMA_TIMEOUT_SECS, config.ollama_timeout_secs.to_string())
+                .parse::<u64>()
+                .map_err(Error::ParseError)?,
+            prompt_template_file: env::var(PROMPT_TEMPLATE_FILE)
+                .ok()
+                .filter(|path| !path.is_empty())
+                .or(config.prompt_template_file),
+            admin_emails: env_or(ADMIN_EMAILS, config.admin_emails),
+            trust_proxy: env::var(TRUST_PROXY)
+                .ok()
+                .filter(|val| !val.is_empty())
+                .map(|val| val == "true" || val == "1")
+                .unwrap_or(config.trust_proxy),
+            oidc_issuer_url: env_or(OIDC_ISSUER_URL, config.oidc_issuer_url),
+            oidc_client_id: env_or(OIDC_CLIENT_ID, config.oidc_client_id),
+            oidc_client_secret: env_or(OIDC_CLIENT_SECRET, config.oidc_client_secret),
+            oidc_redirect_url: env_or(OIDC_REDIRECT_URL, config.oidc_redirect_url),
+            oidc_provision_unverified: env::var(OIDC_PROVISION_UNVERIFIED)
+                .ok()
+                .filter(|val| !val.is_empty())
+                .map(|val| val == "true" || val == "1")
+                .unwrap_or(config.oidc_provision_unverified),
         })
     }
+
+    /// Mailer for verification and password reset mails, as set by `MAIL_TRANSPORT`
+    pub fn account_mailer(&self) -> Result<AccountMailer, Error> {
+        let from = parse_mailbox(&self.mail_from)?;
+        let mailer: SharedMailer = match self.mail_transport {
+            MailTransport::Smtp => {
+                let credentials = Some((self.smtp_username.clone(), self.smtp_password.clone()))
+                    .filter(|(username, _)| !username.is_empty());
+                Arc::new(SmtpMailer::new(&self.smtp_host, self.smtp_port, self.smtp_security, credentials, from)?)
+            }
+            MailTransport::File => Arc::new(FileMailer::new(Some(self.mail_file.clone()), from)),
+            MailTransport::Stdout => Arc::new(FileMailer::new(None, from)),
+        };
+        Ok(AccountMailer::new(mailer, &self.app_url))
+    }
+
+    /// Model for generated answers as set by `AI_PROVIDER`, unless AI answers are off
+    pub fn ai_provider(&self) -> Result<Option<SharedAiProvider>, Error> {
+        let provider: SharedAiProvider = match self.ai_provider {
+            None => return Ok(None),
+            Some(AiProviderType::Gemini) if self.google_ai_key.is_empty() => {
+                return Err(Error::AiConfigError(format!("{} is not set", GOOGLE_AI_KEY)));
+            }
+            Some(AiProviderType::Gemini) => Arc::new(GeminiProvider::new(
+                &self.gemini_base_url,
+                &self.gemini_model,
+                &self.google_ai_key,
+                Duration::from_secs(self.gemini_timeout_secs),
+            )?),
+            Some(AiProviderType::Openai) => Arc::new(OpenAiProvider::new(
+                &self.openai_base_url,
+                &self.openai_model,
+                &self.openai_api_key,
+                Duration::from_secs(self.openai_timeout_secs),
+            )?),
+            Some(AiProviderType::Ollama) => Arc::new(OllamaProvider::new(
+                &self.ollama_base_url,
+                &self.ollama_model,
+                Duration::from_secs(self.ollama_timeout_secs),
+            )?),
+            Some(AiProviderType::Fake) => Arc::new(FakeAiProvider::new()),
+        };
+        Ok(Some(provider))
+    }
+
+    pub fn admin_emails(&self) -> AdminEmails {
+        AdminEmails::parse(&self.admin_emails)
+    }
+
+    /// Template generated answers are asked for with
+    pub fn prompt_template(&self) -> Result<PromptTemplate, Error> {
+        match &self.prompt_template_file {
+            Some(path) => PromptTemplate::from_file(path),
+            None => Ok(PromptTemplate::default()),
+        }
+    }
+
+    /// Client for single sign-on, unless `OIDC_ISSUER_URL` is empty
+    pub fn oidc_client(&self) -> Result<Option<OidcClient>, Error> {
+        if self.oidc_issuer_url.is_empty() {
+            return Ok(None);
+        }
+        if self.oidc_client_id.is_empty() {
+            return Err(Error::OidcError(format!("{} is not set", OIDC_CLIENT_ID)));
+        }
+        Ok(Some(OidcClient::new(
+            &self.oidc_issuer_url,
+            &self.oidc_client_id,
+            &self.oidc_client_secret,
+            &self.oidc_redirect_url,
+            self.oidc_provision_unverified,
+        )))
+    }
+
+    /// Keys for access tokens, from the key file if there is one, otherwise
+    /// `PASETO_KEY` (named `PASETO_KEY_ID`) plus `PASETO_PREVIOUS_KEYS`, and
+    /// `PASETO_SECRET_KEY` plus `PASETO_PREVIOUS_PUBLIC_KEYS` for public tokens
+    pub fn key_ring(&self) -> Result<KeyRing, Error> {
+        if let Some(path) = &self.paseto_key_file {
+            return KeyRing::from_file(path);
+        }
+
+        let current = env::var(PASETO_KEY_ID)
+            .ok()
+            .filter(|id| !id.is_empty())
+            .unwrap_or("default".to_string());
+        let mut keys = HashMap::new();
+        for pair in env::var(PASETO_PREVIOUS_KEYS).unwrap_or_default().split(',').filter(|p| !p.is_empty()) {
+            match pair.split_once(':') {
+                Some((id, key)) => keys.insert(id.trim().to_string(), key.to_string()),
+                None => return Err(Error::KeyRingError(format!("{} needs id:key pairs", PASETO_PREVIOUS_KEYS))),
+            };
+        }
+        keys.insert(current.clone(), env::var(PASETO_KEY).unwrap_or_default());
+
+        let secret_key = env::var(PASETO_SECRET_KEY).ok().filter(|key| !key.is_empty());
+        let public_keys: Vec<String> = env::var(PASETO_PREVIOUS_PUBLIC_KEYS)
+            .unwrap_or_default()
+            .split(',')
+            .filter(|key| !key.is_empty())
+            .map(str::to_string)
+            .collect();
+        KeyRing::new(&current, keys)?.with_public_keys(secret_key.as_deref(), &public_keys)
+    }
+}
+
+/// Like `env::var(..).unwrap_or(..)`, but also falls back for the empty values of `.env.example`
+fn env_or(name: &str, default: String) -> String {
+    env::var(name).ok().filter(|val| !val.is_empty()).unwrap_or(default)
 }
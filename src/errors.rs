@@ -10,12 +10,22 @@ use warp::{
     Rejection, Reply,
 };
 
+use crate::types::api_key::Scope;
+
 #[derive(Debug)]
 pub enum Error {
     ParseError(std::num::ParseIntError),
     MissingParameters,
+    InvalidParameter(String),
+    NotFound,
     WrongPassword,
+    TooManyAttempts,
+    EmailNotVerified,
+    WrongSecondFactor,
+    SecondFactorRequired,
+    MissingScope(Scope),
     CannotDecryptToken,
+    InvalidToken,
     Unauthorized,
     ArgonLibraryError(ArgonError),
     PasswordHashLibraryError(PasswordHashError),
@@ -26,6 +36,11 @@ pub enum Error {
     ClientError(APILayerError),
     ServerError(APILayerError),
     MemoryDatabaseError,
+    KeyRingError(String),
+    MailError(String),
+    OidcError(String),
+    AiDisabled,
+    AiConfigError(String),
 }
 
 #[derive(Debug, Clone)]
@@ -45,8 +60,16 @@ impl std::fmt::Display for Error {
         match self {
             Error::ParseError(ref err) => write!(f, "Cannot parse parameter: {}", err),
             Error::MissingParameters => write!(f, "Missing parameter"),
+            Error::InvalidParameter(param) => write!(f, "Invalid parameter: {}", param),
+            Error::NotFound => write!(f, "Resource not found"),
             Error::WrongPassword => write!(f, "Wrong password"),
+            Error::TooManyAttempts => write!(f, "Too many failed logins, try again later"),
+            Error::EmailNotVerified => write!(f, "Email address is not verified"),
+            Error::WrongSecondFactor => write!(f, "Wrong two-factor code"),
+            Error::SecondFactorRequired => write!(f, "Two-factor authentication is required"),
+            Error::MissingScope(scope) => write!(f, "API key lacks the {} scope", scope.as_str()),
             Error::CannotDecryptToken => write!(f, "Cannot decrypt error"),
+            Error::InvalidToken => write!(f, "Token is invalid, expired or revoked"),
             Error::Unauthorized => write!(f, "No permission to change the underlying resource"),
             Error::PasswordHashLibraryError(_) => write!(f, "Wrong password"),
             Error::ArgonLibraryError(_) => write!(f, "Cannot verify password"),
@@ -57,6 +80,11 @@ impl std::fmt::Display for Error {
             Error::ClientError(err) => write!(f, "External Client error: {}", err),
             Error::ServerError(err) => write!(f, "External Server error: {}", err),
             Error::MemoryDatabaseError => write!(f, "Cannot update, invalid data"),
+            Error::KeyRingError(err) => write!(f, "Invalid token keys: {}", err),
+            Error::OidcError(message) => write!(f, "Single sign-on failed: {}", message),
+            Error::MailError(err) => write!(f, "Cannot send mail: {}", err),
+            Error::AiDisabled => write!(f, "AI answers are disabled"),
+            Error::AiConfigError(err) => write!(f, "Invalid AI configuration: {}", err),
 
         }
     }
@@ -104,12 +132,65 @@ pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
             "No permission to change underlying resource".to_string(),
             StatusCode::UNAUTHORIZED,
         ))
+    } else if let Some(Error::InvalidToken) = r.find() {
+        event!(Level::WARN, "Invalid, expired or revoked token");
+        Ok(warp::reply::with_status(
+            Error::InvalidToken.to_string(),
+            StatusCode::UNAUTHORIZED,
+        ))
+    } else if let Some(Error::NotFound) = r.find() {
+        event!(Level::WARN, "Requested resource was not found");
+        Ok(warp::reply::with_status(
+            Error::NotFound.to_string(),
+            StatusCode::NOT_FOUND,
+        ))
     } else if let Some(Error::WrongPassword) = r.find() {
         event!(Level::ERROR, "Entered wrong password");
         Ok(warp::reply::with_status(
             "Wrong E-Mail/Password combination".to_string(),
             StatusCode::UNAUTHORIZED,
         ))
+    } else if let Some(Error::TooManyAttempts) = r.find() {
+        event!(Level::WARN, "Login while locked out");
+        Ok(warp::reply::with_status(
+            Error::TooManyAttempts.to_string(),
+            StatusCode::TOO_MANY_REQUESTS,
+        ))
+    } else if let Some(Error::EmailNotVerified) = r.find() {
+        event!(Level::WARN, "Login before verifying the email address");
+        Ok(warp::reply::with_status(
+            Error::EmailNotVerified.to_string(),
+            StatusCode::FORBIDDEN,
+        ))
+    } else if let Some(Error::WrongSecondFactor) = r.find() {
+        event!(Level::ERROR, "Entered wrong two-factor code");
+        Ok(warp::reply::with_status(
+            Error::WrongSecondFactor.to_string(),
+            StatusCode::UNAUTHORIZED,
+        ))
+    } else if let Some(Error::SecondFactorRequired) = r.find() {
+        event!(Level::WARN, "Staff session without two-factor authentication");
+        Ok(warp::reply::with_status(
+            Error::SecondFactorRequired.to_string(),
+            StatusCode::FORBIDDEN,
+        ))
+    } else if let Some(Error::MissingScope(scope)) = r.find() {
+        event!(Level::WARN, "API key without the {} scope", scope.as_str());
+        Ok(warp::reply::with_status(
+            Error::MissingScope(*scope).to_string(),
+            StatusCode::FORBIDDEN,
+        ))
+    } else if let Some(Error::OidcError(message)) = r.find() {
+        event!(Level::WARN, "Single sign-on failed: {}", message);
+        Ok(warp::reply::with_status(
+            "Single sign-on failed".to_string(),
+            StatusCode::UNAUTHORIZED,
+        ))
+    } else if let Some(Error::AiDisabled) = r.find() {
+        Ok(warp::reply::with_status(
+            Error::AiDisabled.to_string(),
+            StatusCode::SERVICE_UNAVAILABLE,
+        ))
     } else if let Some(Error::MiddlewareReqwestAPIError(e)) = r.find() {
         event!(Level::ERROR, "{}", e);
         Ok(warp::reply::with_status(
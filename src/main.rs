@@ -1,4 +1,5 @@
 #![warn(clippy::all)]
+#![recursion_limit = "256"]
 
 use std::sync::{Arc};
 use tracing_subscriber::fmt::format::FmtSpan;
@@ -10,10 +11,16 @@ use rush::config::DatabaseType;
 use rush::repositories::memory_repository::MemoryRepository;
 use rush::repositories::repository::Repository;
 use rush::repositories::postgres_repository::PostgresRepository;
+use rush::types::account::Role;
+use rush::types::answer::AnswerId;
+use rush::types::api_key::Scope;
+use rush::types::comment::CommentTarget;
+use rush::types::pagination::PageSize;
+use rush::types::question::QuestionId;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let config = config::Config::new().expect("Config can't be set");
+    let config = config::Config::new()?;
 
     let log_filter = format!(
         "handle_errors={},rush={},warp={}",
@@ -39,7 +46,36 @@ async fn main() -> Result<(), Error> {
         }
     };
 
-    let repository_filter = warp::any().map(move || store.clone());
+    let keys = Arc::new(config.key_ring()?);
+    let keys_filter = {
+        let keys = keys.clone();
+        warp::any().map(move || keys.clone())
+    };
+
+    let admin_emails = Arc::new(config.admin_emails());
+    let admin_emails_filter = warp::any().map(move || admin_emails.clone());
+
+    let oidc_client = config.oidc_client()?.map(Arc::new);
+    let oidc_filter = warp::any().map(move || oidc_client.clone());
+
+    let ai_provider = config.ai_provider()?;
+    let ai_filter = warp::any().map(move || ai_provider.clone());
+    let prompt_template = Arc::new(config.prompt_template()?);
+    let prompt_template_filter = warp::any().map(move || prompt_template.clone());
+
+    let mailer = config.account_mailer()?;
+    let mailer_filter = warp::any().map(move || mailer.clone());
+
+    let repository_filter = {
+        let store = store.clone();
+        warp::any().map(move || store.clone())
+    };
+
+    let page_size = PageSize {
+        default: config.page_size,
+        max: config.max_page_size,
+    };
+    let page_size_filter = warp::any().map(move || page_size);
 
 
     tracing_subscriber::fmt()
@@ -53,20 +89,35 @@ async fn main() -> Result<(), Error> {
     let cors = warp::cors()
         .allow_any_origin()
         .allow_header("content-type")
+        .expose_header("link")
         .allow_methods(&[Method::PUT, Method::DELETE, Method::GET, Method::POST]);
 
+    let get_capabilities = warp::get()
+        .and(warp::path("capabilities"))
+        .and(warp::path::end())
+        .and(ai_filter.clone())
+        .and(oidc_filter.clone())
+        .and_then(routes::capabilities::get_capabilities);
+
     let get_questions = warp::get()
         .and(warp::path("questions"))
         .and(warp::path::end())
-        .and(warp::query())
+        .and(routes::question::question_query(page_size))
         .and(repository_filter.clone())
         .and_then(routes::question::get_questions);
 
+    let get_question = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(repository_filter.clone())
+        .and_then(routes::question::get_question);
+
     let update_question = warp::put()
         .and(warp::path("questions"))
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::QuestionsWrite))
         .and(repository_filter.clone())
         .and(warp::body::json())
         .and_then(routes::question::update_question);
@@ -75,14 +126,14 @@ async fn main() -> Result<(), Error> {
         .and(warp::path("questions"))
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::QuestionsWrite))
         .and(repository_filter.clone())
         .and_then(routes::question::delete_question);
 
     let add_question = warp::post()
         .and(warp::path("questions"))
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::QuestionsWrite))
         .and(repository_filter.clone())
         .and(warp::body::json())
         .and_then(routes::question::add_question);
@@ -92,40 +143,405 @@ async fn main() -> Result<(), Error> {
         .and(warp::path::param::<i32>())
         .and(warp::path("answer"))
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::AnswersWrite))
         .and(repository_filter.clone())
+        .and(ai_filter.clone())
+        .and(prompt_template_filter.clone())
         .and_then(routes::question::add_answer);
 
     let add_answer = warp::post()
         .and(warp::path("answers"))
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::AnswersWrite))
         .and(repository_filter.clone())
         .and(warp::body::form())
         .and_then(routes::answer::add_answer);
 
+    let get_answers = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("answers"))
+        .and(warp::path::end())
+        .and(repository_filter.clone())
+        .and_then(routes::answer::get_answers);
+
+    let get_answer = warp::get()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(repository_filter.clone())
+        .and_then(routes::answer::get_answer);
+
+    let update_answer = warp::put()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::AnswersWrite))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::answer::update_answer);
+
+    let delete_answer = warp::delete()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::AnswersWrite))
+        .and(repository_filter.clone())
+        .and_then(routes::answer::delete_answer);
+
+    let accept_answer = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("accept"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::QuestionsWrite))
+        .and(repository_filter.clone())
+        .and_then(routes::question::accept_answer);
+
+    let update_question_status = warp::put()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("status"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::QuestionsWrite))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::question::update_question_status);
+
+    let vote_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("votes"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::VotesWrite))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::question::vote_question);
+
+    let vote_answer = warp::post()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("votes"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::VotesWrite))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::answer::vote_answer);
+
+    let comment_target = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .map(|id| CommentTarget::Question(QuestionId(id)))
+        .or(warp::path("answers")
+            .and(warp::path::param::<i32>())
+            .and(warp::path("comments"))
+            .map(|id| CommentTarget::Answer(AnswerId(id))))
+        .unify();
+
+    let add_comment = warp::post()
+        .and(comment_target)
+        .and(warp::path::end())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::CommentsWrite))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::comment::add_comment);
+
+    let get_comments = warp::get()
+        .and(comment_target)
+        .and(warp::path::end())
+        .and(repository_filter.clone())
+        .and_then(routes::comment::get_comments);
+
+    let delete_comment = warp::delete()
+        .and(comment_target)
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::auth(keys.clone(), store.clone(), Scope::CommentsWrite))
+        .and(repository_filter.clone())
+        .and_then(routes::comment::delete_comment);
+
+    let get_tags = warp::get()
+        .and(warp::path("tags"))
+        .and(warp::path::end())
+        .and(repository_filter.clone())
+        .and_then(routes::tag::get_tags);
+
+    let search = warp::get()
+        .and(warp::path("search"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(repository_filter.clone())
+        .and_then(routes::search::search);
+
+    let update_role = warp::put()
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("role"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_role(keys.clone(), store.clone(), Role::Admin))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::account::update_role);
+
+    let get_moderation_log = warp::get()
+        .and(warp::path("moderation"))
+        .and(warp::path("log"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(page_size_filter)
+        .and(routes::authentication::require_role(keys.clone(), store.clone(), Role::Moderator))
+        .and(repository_filter.clone())
+        .and_then(routes::moderation::get_moderation_log);
+
+    let revoke_sessions = warp::delete()
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("sessions"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_role(keys.clone(), store.clone(), Role::Admin))
+        .and(repository_filter.clone())
+        .and_then(routes::account::revoke_sessions);
+
+    let unlock_account = warp::delete()
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("lock"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_role(keys.clone(), store.clone(), Role::Admin))
+        .and(repository_filter.clone())
+        .and_then(routes::account::unlock);
+
+    let get_profile = warp::get()
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(page_size_filter)
+        .and(repository_filter.clone())
+        .and_then(routes::account::get_profile);
+
+    let get_me = warp::get()
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and_then(routes::account::get_me);
+
+    let update_me = warp::put()
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::account::update_me);
+
+    let delete_me = warp::delete()
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and_then(routes::account::delete_me);
+
+    let add_api_key = warp::post()
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::api_key::add_api_key);
+
+    let get_api_keys = warp::get()
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and_then(routes::api_key::get_api_keys);
+
+    let revoke_api_key = warp::delete()
+        .and(warp::path("api-keys"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and_then(routes::api_key::revoke_api_key);
+
     let registration = warp::post()
         .and(warp::path("registration"))
         .and(warp::path::end())
+        .and(mailer_filter.clone())
         .and(repository_filter.clone())
         .and(warp::body::json())
         .and_then(routes::authentication::register);
 
+    let resend_verification = warp::post()
+        .and(warp::path("email"))
+        .and(warp::path("verification"))
+        .and(warp::path::end())
+        .and(mailer_filter.clone())
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::resend_verification);
+
+    let verify_email = warp::post()
+        .and(warp::path("email"))
+        .and(warp::path("verify"))
+        .and(warp::path::end())
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::verify_email);
+
+    let forgot_password = warp::post()
+        .and(warp::path("password"))
+        .and(warp::path("forgot"))
+        .and(warp::path::end())
+        .and(mailer_filter.clone())
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::forgot_password);
+
+    let reset_password = warp::post()
+        .and(warp::path("password"))
+        .and(warp::path("reset"))
+        .and(warp::path::end())
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::reset_password);
+
     let login = warp::post()
         .and(warp::path("login"))
         .and(warp::path::end())
+        .and(keys_filter.clone())
         .and(repository_filter.clone())
+        .and(admin_emails_filter.clone())
+        .and(routes::authentication::client_ip(config.trust_proxy))
         .and(warp::body::json())
         .and_then(routes::authentication::login);
 
+    let login_second_factor = warp::post()
+        .and(warp::path("login"))
+        .and(warp::path("2fa"))
+        .and(warp::path::end())
+        .and(keys_filter.clone())
+        .and(repository_filter.clone())
+        .and(routes::authentication::client_ip(config.trust_proxy))
+        .and(warp::body::json())
+        .and_then(routes::authentication::login_second_factor);
+
+    let oidc_login = warp::get()
+        .and(warp::path("oidc"))
+        .and(warp::path("login"))
+        .and(warp::path::end())
+        .and(oidc_filter.clone())
+        .and(repository_filter.clone())
+        .and_then(routes::oidc::start_login);
+
+    let oidc_callback = warp::get()
+        .and(warp::path("oidc"))
+        .and(warp::path("callback"))
+        .and(warp::path::end())
+        .and(oidc_filter.clone())
+        .and(keys_filter.clone())
+        .and(repository_filter.clone())
+        .and(admin_emails_filter.clone())
+        .and(warp::query())
+        .and_then(routes::oidc::callback);
+
+    let enroll_two_factor = warp::post()
+        .and(warp::path("2fa"))
+        .and(warp::path("enroll"))
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and_then(routes::two_factor::enroll);
+
+    let confirm_two_factor = warp::post()
+        .and(warp::path("2fa"))
+        .and(warp::path("confirm"))
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::two_factor::confirm);
+
+    let disable_two_factor = warp::post()
+        .and(warp::path("2fa"))
+        .and(warp::path("disable"))
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::two_factor::disable);
+
+    let refresh = warp::post()
+        .and(warp::path("token"))
+        .and(warp::path("refresh"))
+        .and(warp::path::end())
+        .and(keys_filter.clone())
+        .and(repository_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::refresh);
+
+    let logout = warp::post()
+        .and(warp::path("logout"))
+        .and(warp::path::end())
+        .and(routes::authentication::user_auth(keys.clone(), store.clone()))
+        .and(repository_filter.clone())
+        .and_then(routes::authentication::logout);
+
+    let get_public_keys = warp::get()
+        .and(warp::path(".well-known"))
+        .and(warp::path("paseto-keys"))
+        .and(warp::path::end())
+        .and(keys_filter.clone())
+        .and_then(routes::authentication::get_public_keys);
+
     let routes = get_questions
+        .or(get_question)
         .or(update_question)
         .or(add_question)
         .or(delete_question)
         .or(add_ai_answer)
         .or(add_answer)
+        .or(get_answers)
+        .or(get_answer)
+        .or(update_answer)
+        .or(delete_answer)
+        .or(accept_answer)
+        .or(update_question_status)
+        .or(vote_question)
+        .or(vote_answer)
+        .or(add_comment)
+        .or(get_comments)
+        .or(delete_comment)
+        .or(get_tags)
+        .or(search)
+        .or(update_role)
+        .or(get_moderation_log)
+        .or(revoke_sessions)
+        .or(unlock_account)
+        .or(get_profile)
+        .or(get_me)
+        .or(update_me)
+        .or(delete_me)
+        .or(add_api_key)
+        .or(get_api_keys)
+        .or(revoke_api_key)
         .or(registration)
+        .or(resend_verification)
+        .or(verify_email)
+        .or(forgot_password)
+        .or(reset_password)
         .or(login)
+        .or(login_second_factor)
+        .or(oidc_login)
+        .or(oidc_callback)
+        .or(enroll_two_factor)
+        .or(confirm_two_factor)
+        .or(disable_two_factor)
+        .or(refresh)
+        .or(logout)
+        .or(get_public_keys)
+        .or(get_capabilities)
         .with(cors)
         .with(warp::trace::request())
         .recover(return_error);
@@ -1,13 +1,27 @@
-
+use std::collections::HashMap;
 
 use async_trait::async_trait;
+use chrono::{NaiveDateTime, SubsecRound, Utc};
+use tokio::sync::RwLock;
 
 use crate::errors::Error;
 use crate::repositories::repository::{RepositoryPort};
 use crate::stores::memory_store::MemoryStore;
-use crate::types::account::{Account, AccountId};
+use crate::types::account::{Account, AccountId, ProfileUpdate, Role};
 use crate::types::answer::{Answer, AnswerId, NewAnswer};
-use crate::types::question::{NewQuestion, Question, QuestionId};
+use crate::types::api_key::{ApiKey, ApiKeyId, NewApiKey};
+use crate::types::comment::{Comment, CommentId, CommentTarget, NewComment};
+use crate::types::login_attempt::{LoginFailures, LoginKey};
+use crate::types::moderation::{Actor, ModerationAction, ModerationEntry};
+use crate::types::oidc::OidcLogin;
+use crate::types::pagination::{Cursor, Page, Pagination};
+use crate::types::prompt::KnowledgeSource;
+use crate::types::question::{NewQuestion, Question, QuestionId, QuestionQuery, QuestionSort, QuestionStatus};
+use crate::types::search::{tokenize, SearchResult};
+use crate::types::tag::TagCount;
+use crate::types::token::{AccountToken, AccountTokenPurpose, NewAccountToken, NewRefreshToken, RefreshToken};
+use crate::types::totp::Totp;
+use crate::types::vote::VoteDirection;
 
 #[derive(Debug, Clone)]
 pub struct MemoryRepository {
@@ -26,79 +40,999 @@ impl MemoryRepository {
             store: MemoryStore::new(),
         }
     }
+
+    /// Hands out the next id of the given counter, holding the write lock
+    /// so two concurrent inserts can never get the same id
+    async fn next_id(index: &RwLock<i32>) -> i32 {
+        let mut index = index.write().await;
+        let id = *index;
+        *index += 1;
+        id
+    }
+
+    /// Adds an entry to the moderation log when a moderator changed someone else's content
+    async fn record_moderation(&self, actor: &Actor, action: ModerationAction, target_id: i32, author_id: Option<AccountId>) {
+        if let Actor::Moderator(moderator_id) = actor {
+            let mut log = self.store.moderation_log.write().await;
+            let id = log.len() as i32 + 1;
+            log.push(ModerationEntry {
+                id,
+                moderator_id: moderator_id.clone(),
+                action,
+                target_id,
+                author_id,
+                created_on: Utc::now().naive_utc(),
+            });
+        }
+    }
+
+    /// Share of the search terms found in the text, between 0 and 1
+    fn match_ratio(terms: &[String], text: &str) -> f32 {
+        let words = tokenize(text);
+        let hits = terms.iter().filter(|term| words.contains(term)).count();
+        hits as f32 / terms.len() as f32
+    }
+
+    /// Up to 30 words around the first hit, with every hit wrapped in `<mark>` like `ts_headline`
+    fn snippet(terms: &[String], text: &str) -> String {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let is_hit = |word: &str| tokenize(word).iter().any(|w| terms.contains(w));
+        let first = words.iter().position(|w| is_hit(w)).unwrap_or(0);
+        let start = first.saturating_sub(10);
+
+        words
+            .iter()
+            .skip(start)
+            .take(30)
+            .map(|w| if is_hit(w) { format!("<mark>{}</mark>", w) } else { w.to_string() })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Questions and answers containing any of `terms`, best match first
+    async fn search_terms(&self, terms: Vec<String>, limit: i64) -> Result<Vec<SearchResult>, Error> {
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let questions = self.store.questions.read().await;
+        let mut results = Vec::new();
+        for question in questions.values() {
+            // Title hits weigh double, like the 'A' weight on the Postgres column
+            let rank = 2.0 * Self::match_ratio(&terms, &question.title)
+                + Self::match_ratio(&terms, &question.content);
+            if rank > 0.0 {
+                results.push(SearchResult {
+                    question_id: question.id,
+                    answer_id: None,
+                    title: question.title.clone(),
+                    snippet: Self::snippet(&terms, &question.content),
+                    rank,
+                });
+            }
+        }
+        for answer in self.store.answers.read().await.values() {
+            let rank = Self::match_ratio(&terms, &answer.content);
+            if let (true, Some(question)) = (rank > 0.0, questions.get(&answer.question_id)) {
+                results.push(SearchResult {
+                    question_id: question.id,
+                    answer_id: Some(answer.id.clone()),
+                    title: question.title.clone(),
+                    snippet: Self::snippet(&terms, &answer.content),
+                    rank,
+                });
+            }
+        }
+
+        results.sort_by(|a, b| b.rank.total_cmp(&a.rank).then_with(|| a.question_id.0.cmp(&b.question_id.0)));
+        results.truncate(limit.max(0) as usize);
+        Ok(results)
+    }
+
+    /// Status a question falls back to when it is (re)opened or loses its accepted answer
+    fn derived_status(question: &Question, answers: &HashMap<AnswerId, Answer>) -> QuestionStatus {
+        if question.accepted_answer_id.is_some() {
+            QuestionStatus::Resolved
+        } else if answers.values().any(|a| a.question_id == question.id) {
+            QuestionStatus::Answered
+        } else {
+            QuestionStatus::Open
+        }
+    }
 }
 
 #[async_trait]
 impl RepositoryPort for MemoryRepository {
-    async fn get_questions(&self, _limit: Option<i32>, _offset: i32) -> Result<Vec<Question>, Error> {
-        let res = self.store.questions.read().await.values().cloned().collect();
-        Ok(res)
+    async fn get_questions(&self, query: QuestionQuery) -> Result<Page<Question>, Error> {
+        let questions = self.store.questions.read().await;
+        let answers = self.store.answers.read().await;
+
+        // Number of answers and time of the newest one, per question
+        let mut activity: HashMap<QuestionId, (usize, NaiveDateTime)> = HashMap::new();
+        for answer in answers.values() {
+            let created_on = answer.created_on.unwrap_or_default();
+            let entry = activity.entry(answer.question_id).or_insert((0, created_on));
+            entry.0 += 1;
+            entry.1 = entry.1.max(created_on);
+        }
+        let answer_count = |q: &Question| activity.get(&q.id).map_or(0, |(count, _)| *count);
+        let last_activity = |q: &Question| {
+            let created_on = q.created_on.unwrap_or_default();
+            activity.get(&q.id).map_or(created_on, |(_, last)| created_on.max(*last))
+        };
+
+        let mut res: Vec<Question> = questions
+            .values()
+            .filter(|q| query.matches(q))
+            .filter(|q| match query.has_answers {
+                Some(has_answers) => has_answers == (answer_count(q) > 0),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        // Cursors carry microseconds, like the `timestamp` column in Postgres
+        let key = |q: &Question| (q.created_on.unwrap_or_default().trunc_subsecs(6), q.id.0);
+        match query.sort {
+            QuestionSort::Newest => res.sort_by_key(|q| std::cmp::Reverse(key(q))),
+            QuestionSort::Oldest => res.sort_by_key(key),
+            QuestionSort::Score => res.sort_by_key(|q| (std::cmp::Reverse(q.score), key(q))),
+            QuestionSort::Activity => {
+                res.sort_by_key(|q| std::cmp::Reverse((last_activity(q), key(q))))
+            }
+            QuestionSort::Unanswered => res.sort_by_key(|q| (answer_count(q), key(q))),
+        }
+
+        let pagination = query.pagination;
+        let total = res.len() as i64;
+        let keyset = query.sort.is_keyset();
+        // Whether `a` comes after `b` in display order
+        let follows = |a: (NaiveDateTime, i32), b: (NaiveDateTime, i32)| match query.sort {
+            QuestionSort::Newest => a < b,
+            _ => a > b,
+        };
+        // One extra item tells whether there is another page
+        let take = pagination.limit as usize + 1;
+        let res: Vec<Question> = match (&pagination.cursor, keyset) {
+            (Some(Cursor::After(created_on, id)), true) => res
+                .into_iter()
+                .filter(|q| follows(key(q), (*created_on, *id)))
+                .take(take)
+                .collect(),
+            (Some(Cursor::Before(created_on, id)), true) => {
+                let before: Vec<Question> = res
+                    .into_iter()
+                    .filter(|q| follows((*created_on, *id), key(q)))
+                    .collect();
+                let start = before.len().saturating_sub(take);
+                before[start..].to_vec()
+            }
+            (Some(Cursor::After(..) | Cursor::Before(..)), false) => {
+                return Err(Error::InvalidParameter("cursor".to_string()))
+            }
+            (Some(Cursor::Offset(offset)), _) => res.into_iter().skip(*offset as usize).take(take).collect(),
+            (None, _) => res.into_iter().take(take).collect(),
+        };
+
+        Ok(Page::from_fetched(res, total, &pagination, keyset, key))
     }
 
     async fn get_question(&self, question_id: i32) -> Result<Question, Error> {
         match self.store.questions.read().await.get(&QuestionId(question_id)) {
-            None => Err(Error::MemoryDatabaseError),
+            None => Err(Error::NotFound),
             Some(question) => Ok(question.clone())
         }
     }
 
-    async fn is_question_owner(&self, _question_id: i32, _account_id: &AccountId) -> Result<bool, Error> {
-        todo!()
+    async fn is_question_owner(&self, question_id: i32, account_id: &AccountId) -> Result<bool, Error> {
+        match self.store.questions.read().await.get(&QuestionId(question_id)) {
+            Some(question) => Ok(question.account_id.as_ref() == Some(account_id)),
+            None => Ok(false),
+        }
     }
 
-    async fn add_question(&self, new_question: NewQuestion, _account_id: AccountId) -> Result<Question, Error> {
-        let question_id = QuestionId(*self.store.question_index.read().await);
-        *self.store.question_index.write().await += 1;
-        match self
-            .store.questions
+    async fn add_question(&self, new_question: NewQuestion, account_id: AccountId) -> Result<Question, Error> {
+        let question_id = QuestionId(Self::next_id(&self.store.question_index).await);
+        let question = Question {
+            id: question_id,
+            title: new_question.title,
+            content: new_question.content,
+            tags: new_question.tags,
+            account_id: Some(account_id),
+            created_on: Some(Utc::now().naive_utc()),
+            score: 0,
+            status: QuestionStatus::Open,
+            accepted_answer_id: None,
+        };
+
+        self.store.questions.write().await.insert(question_id, question.clone());
+        Ok(question)
+    }
+
+    async fn update_question(&self, question: Question, id: i32, actor: Actor) -> Result<Question, Error> {
+        if let Actor::Author(account_id) = &actor {
+            if !self.is_question_owner(id, account_id).await? {
+                return Err(Error::MemoryDatabaseError);
+            }
+        }
+
+        // Like the Postgres `UPDATE`, only the title, content and tags can change
+        let updated = match self.store.questions.write().await.get_mut(&QuestionId(id)) {
+            Some(q) => {
+                q.title = question.title;
+                q.content = question.content;
+                q.tags = question.tags;
+                q.clone()
+            }
+            None => return Err(Error::NotFound),
+        };
+        self.record_moderation(&actor, ModerationAction::UpdateQuestion, id, updated.account_id.clone()).await;
+        Ok(updated)
+    }
+
+    async fn delete_question(&self, id: i32, actor: Actor) -> Result<bool, Error> {
+        if let Actor::Author(account_id) = &actor {
+            if !self.is_question_owner(id, account_id).await? {
+                return Err(Error::MemoryDatabaseError);
+            }
+        }
+
+        let removed = self.store.questions.write().await.remove(&QuestionId(id));
+        match removed {
+            Some(question) => {
+                // Same as the `ON DELETE CASCADE` on the Postgres tables
+                let target = CommentTarget::Question(QuestionId(id));
+                self.store.comments.write().await.retain(|_, c| !target.matches(c));
+                self.store.question_votes.write().await.retain(|(q, _), _| *q != QuestionId(id));
+                self.record_moderation(&actor, ModerationAction::DeleteQuestion, id, question.account_id).await;
+                Ok(true)
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn vote_question(&self, id: i32, account_id: AccountId, direction: VoteDirection) -> Result<Question, Error> {
+        let mut questions = self.store.questions.write().await;
+        let question = match questions.get_mut(&QuestionId(id)) {
+            Some(q) => q,
+            None => return Err(Error::NotFound),
+        };
+
+        let previous = self
+            .store
+            .question_votes
             .write()
             .await
-            .insert(question_id, Question {
-                id: question_id,
-                title: new_question.title,
-                content: new_question.content,
-                tags: new_question.tags,
-            }) {
-            Some(q) => Ok(q),
-            None => Err(Error::MemoryDatabaseError)
+            .insert((QuestionId(id), account_id), direction.value())
+            .unwrap_or(0);
+        question.score += direction.value() - previous;
+        Ok(question.clone())
+    }
+
+    async fn accept_answer(&self, question_id: i32, answer_id: i32, account_id: AccountId) -> Result<Question, Error> {
+        let mut questions = self.store.questions.write().await;
+        let question = match questions.get_mut(&QuestionId(question_id)) {
+            Some(q) if q.account_id == Some(account_id) => q,
+            _ => return Err(Error::NotFound),
+        };
+
+        match self.store.answers.read().await.get(&AnswerId(answer_id)) {
+            Some(answer) if answer.question_id == question.id => {
+                question.accepted_answer_id = Some(answer.id.clone());
+                question.status = QuestionStatus::Resolved;
+                Ok(question.clone())
+            }
+            _ => Err(Error::NotFound),
         }
     }
 
-    async fn update_question(&self, question: Question, id: i32, _account_id: AccountId) -> Result<Question, Error> {
-        match self.store.questions.write().await.get_mut(&QuestionId(id)) {
-            Some(q) => *q = question.clone(),
-            None => return Err(Error::MemoryDatabaseError),
+    async fn update_question_status(&self, id: i32, status: QuestionStatus, account_id: AccountId) -> Result<Question, Error> {
+        let mut questions = self.store.questions.write().await;
+        let question = match questions.get_mut(&QuestionId(id)) {
+            Some(q) if q.account_id == Some(account_id) => q,
+            _ => return Err(Error::MemoryDatabaseError),
         };
-        Ok(question)
+
+        question.status = match status {
+            QuestionStatus::Closed => QuestionStatus::Closed,
+            _ => Self::derived_status(question, &*self.store.answers.read().await),
+        };
+        Ok(question.clone())
     }
 
-    async fn delete_question(&self, id: i32, _account_id: AccountId) -> Result<bool, Error> {
-        match self.store.questions.write().await.remove(&QuestionId(id)) {
-            Some(_) => Ok(true),
+    async fn add_answer(&self, new_answer: NewAnswer, account_id: AccountId) -> Result<Answer, Error> {
+        let mut questions = self.store.questions.write().await;
+        // Mirrors the foreign key on `answers.corresponding_question`
+        let question = match questions.get_mut(&new_answer.question_id) {
+            Some(q) => q,
             None => return Err(Error::MemoryDatabaseError),
+        };
+        if question.status == QuestionStatus::Open {
+            question.status = QuestionStatus::Answered;
         }
-    }
 
-    async fn add_answer(&self, new_answer: NewAnswer, _account_id: AccountId) -> Result<Answer, Error> {
-        let id = AnswerId(*self.store.answer_index.read().await);
-        *self.store.answer_index.write().await += 1;
+        let id = AnswerId(Self::next_id(&self.store.answer_index).await);
         let answer = Answer {
-            id,
+            id: id.clone(),
             content: new_answer.content,
             question_id: new_answer.question_id,
+            account_id: Some(account_id),
+            created_on: Some(Utc::now().naive_utc()),
+            score: 0,
+            prompt_version: new_answer.prompt_version,
         };
 
-        match self.store.answers.write().await.insert(answer.id.clone(), answer) {
-            Some(a) => Ok(a),
-            None => return Err(Error::MemoryDatabaseError),
+        self.store.answers.write().await.insert(id, answer.clone());
+        Ok(answer)
+    }
+
+    async fn get_answers(&self, question_id: i32) -> Result<Vec<Answer>, Error> {
+        let mut answers: Vec<Answer> = self
+            .store
+            .answers
+            .read()
+            .await
+            .values()
+            .filter(|answer| answer.question_id == QuestionId(question_id))
+            .cloned()
+            .collect();
+        // Ids are handed out in insertion order, so this matches `ORDER BY created_on`
+        answers.sort_by_key(|answer| answer.id.0);
+        Ok(answers)
+    }
+
+    async fn get_answer(&self, answer_id: i32) -> Result<Answer, Error> {
+        match self.store.answers.read().await.get(&AnswerId(answer_id)) {
+            None => Err(Error::NotFound),
+            Some(answer) => Ok(answer.clone())
+        }
+    }
+
+    async fn is_answer_owner(&self, answer_id: i32, account_id: &AccountId) -> Result<bool, Error> {
+        match self.store.answers.read().await.get(&AnswerId(answer_id)) {
+            Some(answer) => Ok(answer.account_id.as_ref() == Some(account_id)),
+            None => Ok(false),
+        }
+    }
+
+    async fn update_answer(&self, answer: Answer, id: i32, actor: Actor) -> Result<Answer, Error> {
+        if let Actor::Author(account_id) = &actor {
+            if !self.is_answer_owner(id, account_id).await? {
+                return Err(Error::MemoryDatabaseError);
+            }
+        }
+
+        // Only the content can change, an answer never moves to another question
+        let updated = match self.store.answers.write().await.get_mut(&AnswerId(id)) {
+            Some(a) => {
+                a.content = answer.content;
+                a.clone()
+            }
+            None => return Err(Error::NotFound),
+        };
+        self.record_moderation(&actor, ModerationAction::UpdateAnswer, id, updated.account_id.clone()).await;
+        Ok(updated)
+    }
+
+    async fn delete_answer(&self, id: i32, actor: Actor) -> Result<bool, Error> {
+        if let Actor::Author(account_id) = &actor {
+            if !self.is_answer_owner(id, account_id).await? {
+                return Err(Error::MemoryDatabaseError);
+            }
+        }
+
+        let removed = self.store.answers.write().await.remove(&AnswerId(id));
+        match removed {
+            Some(answer) => {
+                // Same as the `ON DELETE CASCADE` on the Postgres tables
+                let target = CommentTarget::Answer(AnswerId(id));
+                self.store.comments.write().await.retain(|_, c| !target.matches(c));
+                self.store.answer_votes.write().await.retain(|(a, _), _| *a != AnswerId(id));
+
+                // The question may have lost its only or its accepted answer
+                let mut questions = self.store.questions.write().await;
+                if let Some(question) = questions.get_mut(&answer.question_id) {
+                    if question.accepted_answer_id == Some(AnswerId(id)) {
+                        question.accepted_answer_id = None;
+                    }
+                    if question.status != QuestionStatus::Closed {
+                        question.status = Self::derived_status(question, &*self.store.answers.read().await);
+                    }
+                }
+                self.record_moderation(&actor, ModerationAction::DeleteAnswer, id, answer.account_id).await;
+                Ok(true)
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn vote_answer(&self, id: i32, account_id: AccountId, direction: VoteDirection) -> Result<Answer, Error> {
+        let mut answers = self.store.answers.write().await;
+        let answer = match answers.get_mut(&AnswerId(id)) {
+            Some(a) => a,
+            None => return Err(Error::NotFound),
+        };
+
+        let previous = self
+            .store
+            .answer_votes
+            .write()
+            .await
+            .insert((AnswerId(id), account_id), direction.value())
+            .unwrap_or(0);
+        answer.score += direction.value() - previous;
+        Ok(answer.clone())
+    }
+
+    async fn add_comment(&self, target: CommentTarget, new_comment: NewComment, account_id: AccountId) -> Result<Comment, Error> {
+        // Mirrors the foreign keys on `comments.question_id` and `comments.answer_id`
+        let (question_id, answer_id) = match target {
+            CommentTarget::Question(id) => {
+                if !self.store.questions.read().await.contains_key(&id) {
+                    return Err(Error::MemoryDatabaseError);
+                }
+                (Some(id), None)
+            }
+            CommentTarget::Answer(id) => {
+                if !self.store.answers.read().await.contains_key(&id) {
+                    return Err(Error::MemoryDatabaseError);
+                }
+                (None, Some(id))
+            }
+        };
+
+        let id = CommentId(Self::next_id(&self.store.comment_index).await);
+        let comment = Comment {
+            id: id.clone(),
+            content: new_comment.content,
+            question_id,
+            answer_id,
+            account_id: Some(account_id),
+            created_on: Some(Utc::now().naive_utc()),
+        };
+
+        self.store.comments.write().await.insert(id, comment.clone());
+        Ok(comment)
+    }
+
+    async fn get_comments(&self, target: CommentTarget) -> Result<Vec<Comment>, Error> {
+        let mut comments: Vec<Comment> = self
+            .store
+            .comments
+            .read()
+            .await
+            .values()
+            .filter(|comment| target.matches(comment))
+            .cloned()
+            .collect();
+        comments.sort_by_key(|comment| comment.id.0);
+        Ok(comments)
+    }
+
+    async fn is_comment_owner(&self, comment_id: i32, account_id: &AccountId) -> Result<bool, Error> {
+        match self.store.comments.read().await.get(&CommentId(comment_id)) {
+            Some(comment) => Ok(comment.account_id.as_ref() == Some(account_id)),
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_comment(&self, target: CommentTarget, id: i32, account_id: AccountId) -> Result<bool, Error> {
+        let mut comments = self.store.comments.write().await;
+        match comments.get(&CommentId(id)) {
+            Some(comment) if target.matches(comment) && comment.account_id == Some(account_id) => {
+                comments.remove(&CommentId(id));
+                Ok(true)
+            }
+            _ => Err(Error::NotFound),
+        }
+    }
+
+    async fn get_tags(&self) -> Result<Vec<TagCount>, Error> {
+        let mut counts: HashMap<String, i64> = HashMap::new();
+        for question in self.store.questions.read().await.values() {
+            let mut tags = question.tags.clone().unwrap_or_default();
+            // A tag listed twice on one question still counts once, like `COUNT(DISTINCT id)`
+            tags.sort();
+            tags.dedup();
+            for tag in tags {
+                *counts.entry(tag).or_insert(0) += 1;
+            }
         }
+
+        let mut tags: Vec<TagCount> = counts
+            .into_iter()
+            .map(|(tag, count)| TagCount { tag, count })
+            .collect();
+        tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+        Ok(tags)
+    }
+
+    async fn search(&self, query: String, limit: i64) -> Result<Vec<SearchResult>, Error> {
+        self.search_terms(tokenize(&query), limit).await
     }
 
-    async fn add_account(&self, _account: Account) -> Result<bool, Error> {
-        todo!()
+    async fn search_any(&self, terms: &[String], limit: i64) -> Result<Vec<SearchResult>, Error> {
+        let mut words: Vec<String> = Vec::new();
+        for word in terms.iter().flat_map(|term| tokenize(term)) {
+            if !words.contains(&word) {
+                words.push(word);
+            }
+        }
+        self.search_terms(words, limit).await
     }
 
-    async fn get_account(&self, _email: String) -> Result<Account, Error> {
-        todo!()
+    async fn get_knowledge_sources(&self, question_ids: &[QuestionId]) -> Result<Vec<KnowledgeSource>, Error> {
+        let questions = self.store.questions.read().await;
+        let answers = self.store.answers.read().await;
+        let mut sources = Vec::new();
+        for question in question_ids.iter().filter_map(|id| questions.get(id)) {
+            if !matches!(question.status, QuestionStatus::Answered | QuestionStatus::Resolved) {
+                continue;
+            }
+            let answer = match question.accepted_answer_id.as_ref().and_then(|id| answers.get(id)) {
+                Some(answer) => Some(answer),
+                // Ties go to the newest answer, like `ORDER BY score DESC, created_on DESC`
+                None => answers
+                    .values()
+                    .filter(|answer| answer.question_id == question.id && answer.prompt_version.is_none())
+                    .max_by_key(|answer| (answer.score, answer.id.0)),
+            };
+            if let Some(answer) = answer {
+                sources.push(KnowledgeSource {
+                    question_id: question.id,
+                    title: question.title.clone(),
+                    answer: answer.content.clone(),
+                });
+            }
+        }
+        Ok(sources)
     }
-}
\ No newline at end of file
+
+    async fn add_account(&self, account: Account) -> Result<bool, Error> {
+        let mut accounts = self.store.accounts.write().await;
+        // The email is the primary key of the accounts table
+        if accounts.contains_key(&account.email) {
+            return Err(Error::MemoryDatabaseError);
+        }
+
+        let id = AccountId(Self::next_id(&self.store.account_index).await);
+        accounts.insert(
+            account.email.clone(),
+            Account {
+                id: Some(id),
+                email: account.email,
+                password: account.password,
+                role: account.role,
+                email_verified: account.email_verified,
+                display_name: account.display_name,
+                avatar_url: account.avatar_url,
+                bio: account.bio,
+                created_on: Some(Utc::now().naive_utc()),
+            },
+        );
+        Ok(true)
+    }
+
+    async fn get_account(&self, email: String) -> Result<Account, Error> {
+        match self.store.accounts.read().await.get(&email) {
+            Some(account) => Ok(account.clone()),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn get_account_by_id(&self, account_id: AccountId) -> Result<Account, Error> {
+        match self
+            .store
+            .accounts
+            .read()
+            .await
+            .values()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => Ok(account.clone()),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn update_account_role(&self, account_id: AccountId, role: Role) -> Result<bool, Error> {
+        match self
+            .store
+            .accounts
+            .write()
+            .await
+            .values_mut()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => {
+                account.role = role;
+                Ok(true)
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn update_account_password(&self, account_id: AccountId, password: String) -> Result<bool, Error> {
+        match self
+            .store
+            .accounts
+            .write()
+            .await
+            .values_mut()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => {
+                account.password = password;
+                Ok(true)
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn verify_account_email(&self, account_id: AccountId) -> Result<bool, Error> {
+        match self
+            .store
+            .accounts
+            .write()
+            .await
+            .values_mut()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => {
+                account.email_verified = true;
+                Ok(true)
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn update_account_profile(&self, account_id: AccountId, profile: ProfileUpdate) -> Result<Account, Error> {
+        match self
+            .store
+            .accounts
+            .write()
+            .await
+            .values_mut()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => {
+                account.display_name = profile.display_name;
+                account.avatar_url = profile.avatar_url;
+                account.bio = profile.bio;
+                Ok(account.clone())
+            }
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn delete_account(&self, account_id: AccountId) -> Result<bool, Error> {
+        let mut accounts = self.store.accounts.write().await;
+        let email = match accounts.values().find(|account| account.id.as_ref() == Some(&account_id)) {
+            Some(account) => account.email.clone(),
+            None => return Ok(false),
+        };
+        accounts.remove(&email);
+
+        self.store.refresh_tokens.write().await.retain(|_, token| token.account_id != account_id);
+        self.store.account_tokens.write().await.retain(|_, token| token.account_id != account_id);
+        self.store.totp.write().await.remove(&account_id);
+        self.store.recovery_codes.write().await.remove(&account_id);
+        self.store.api_keys.write().await.retain(|_, api_key| api_key.account_id != account_id);
+        self.store.login_failures.write().await.remove(&LoginKey::Email(email).to_string());
+        Ok(true)
+    }
+
+    async fn get_account_answers(&self, account_id: AccountId, limit: i64) -> Result<Vec<Answer>, Error> {
+        let mut answers: Vec<Answer> = self
+            .store
+            .answers
+            .read()
+            .await
+            .values()
+            .filter(|answer| answer.account_id.as_ref() == Some(&account_id))
+            .cloned()
+            .collect();
+        answers.sort_by_key(|answer| std::cmp::Reverse(answer.id.0));
+        answers.truncate(limit.max(0) as usize);
+        Ok(answers)
+    }
+
+    async fn add_account_token(&self, token: NewAccountToken) -> Result<AccountToken, Error> {
+        let id = Self::next_id(&self.store.account_token_index).await;
+        let token = AccountToken {
+            id,
+            account_id: token.account_id,
+            purpose: token.purpose,
+            token_hash: token.token_hash,
+            expires_on: token.expires_on,
+            used_on: None,
+        };
+        self.store.account_tokens.write().await.insert(id, token.clone());
+        Ok(token)
+    }
+
+    async fn use_account_token(&self, purpose: AccountTokenPurpose, token_hash: String) -> Result<AccountId, Error> {
+        let now = Utc::now().naive_utc();
+        let mut tokens = self.store.account_tokens.write().await;
+        let account_id = match tokens
+            .values()
+            .find(|token| token.token_hash == token_hash && token.is_usable(purpose, now))
+        {
+            Some(token) => token.account_id.clone(),
+            None => return Err(Error::NotFound),
+        };
+
+        for token in tokens.values_mut() {
+            if token.account_id == account_id && token.purpose == purpose && token.used_on.is_none() {
+                token.used_on = Some(now);
+            }
+        }
+        Ok(account_id)
+    }
+
+    async fn get_moderation_log(&self, pagination: Pagination) -> Result<Page<ModerationEntry>, Error> {
+        let log = self.store.moderation_log.read().await;
+        let offset = match pagination.cursor {
+            None => 0,
+            Some(Cursor::Offset(offset)) => offset as usize,
+            Some(_) => return Err(Error::InvalidParameter("cursor".to_string())),
+        };
+
+        // Newest entry first
+        let entries: Vec<ModerationEntry> = log
+            .iter()
+            .rev()
+            .skip(offset)
+            .take(pagination.limit as usize + 1)
+            .cloned()
+            .collect();
+        Ok(Page::from_fetched(entries, log.len() as i64, &pagination, false, |e| (e.created_on, e.id)))
+    }
+
+    async fn get_totp(&self, account_id: AccountId) -> Result<Totp, Error> {
+        match self.store.totp.read().await.get(&account_id) {
+            Some(totp) => Ok(totp.clone()),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn set_totp_secret(&self, account_id: AccountId, secret: String) -> Result<bool, Error> {
+        let mut totp = self.store.totp.write().await;
+        if totp.get(&account_id).map(Totp::is_enabled).unwrap_or(false) {
+            return Ok(false);
+        }
+        totp.insert(
+            account_id.clone(),
+            Totp {
+                account_id,
+                secret,
+                enabled_on: None,
+                last_used_step: None,
+            },
+        );
+        Ok(true)
+    }
+
+    async fn enable_totp(&self, account_id: AccountId, step: i64, recovery_code_hashes: Vec<String>) -> Result<bool, Error> {
+        match self.store.totp.write().await.get_mut(&account_id) {
+            Some(totp) if !totp.is_enabled() => {
+                totp.enabled_on = Some(Utc::now().naive_utc());
+                totp.last_used_step = Some(step);
+            }
+            _ => return Ok(false),
+        }
+        self.store.recovery_codes.write().await.insert(account_id, recovery_code_hashes);
+        Ok(true)
+    }
+
+    async fn disable_totp(&self, account_id: AccountId) -> Result<bool, Error> {
+        self.store.recovery_codes.write().await.remove(&account_id);
+        Ok(self.store.totp.write().await.remove(&account_id).is_some())
+    }
+
+    async fn use_totp_step(&self, account_id: AccountId, step: i64) -> Result<bool, Error> {
+        match self.store.totp.write().await.get_mut(&account_id) {
+            Some(totp) if totp.is_enabled() && totp.last_used_step.map(|last| step > last).unwrap_or(true) => {
+                totp.last_used_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn use_recovery_code(&self, account_id: AccountId, code_hash: String) -> Result<bool, Error> {
+        match self.store.recovery_codes.write().await.get_mut(&account_id) {
+            Some(codes) => match codes.iter().position(|hash| *hash == code_hash) {
+                Some(index) => {
+                    codes.remove(index);
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+            None => Ok(false),
+        }
+    }
+
+    async fn get_login_failures(&self, key: String) -> Result<Option<LoginFailures>, Error> {
+        Ok(self.store.login_failures.read().await.get(&key).cloned())
+    }
+
+    async fn add_login_failure(&self, key: String, window_start: NaiveDateTime) -> Result<LoginFailures, Error> {
+        let now = Utc::now().naive_utc();
+        let mut login_failures = self.store.login_failures.write().await;
+        let entry = login_failures.entry(key.clone()).or_insert(LoginFailures {
+            key,
+            failures: 0,
+            last_failure_on: now,
+            locked_until: None,
+        });
+        if entry.last_failure_on < window_start {
+            entry.failures = 0;
+        }
+        entry.failures += 1;
+        entry.last_failure_on = now;
+        Ok(entry.clone())
+    }
+
+    async fn lock_login(&self, key: String, until: NaiveDateTime) -> Result<bool, Error> {
+        match self.store.login_failures.write().await.get_mut(&key) {
+            Some(entry) => {
+                entry.locked_until = Some(until);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn clear_login_failures(&self, key: String) -> Result<bool, Error> {
+        Ok(self.store.login_failures.write().await.remove(&key).is_some())
+    }
+
+    async fn add_refresh_token(&self, token: NewRefreshToken) -> Result<RefreshToken, Error> {
+        let id = Self::next_id(&self.store.refresh_token_index).await;
+        let token = RefreshToken {
+            id,
+            account_id: token.account_id,
+            token_hash: token.token_hash,
+            expires_on: token.expires_on,
+            revoked_on: None,
+            mfa: token.mfa,
+        };
+        self.store.refresh_tokens.write().await.insert(id, token.clone());
+        Ok(token)
+    }
+
+    async fn get_refresh_token(&self, token_hash: String) -> Result<RefreshToken, Error> {
+        match self
+            .store
+            .refresh_tokens
+            .read()
+            .await
+            .values()
+            .find(|token| token.token_hash == token_hash)
+        {
+            Some(token) => Ok(token.clone()),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn rotate_refresh_token(
+        &self,
+        id: i32,
+        token_hash: String,
+        new_token_hash: String,
+        expires_on: NaiveDateTime,
+    ) -> Result<bool, Error> {
+        match self.store.refresh_tokens.write().await.get_mut(&id) {
+            Some(token) if token.token_hash == token_hash && token.is_active(Utc::now().naive_utc()) => {
+                token.token_hash = new_token_hash;
+                token.expires_on = expires_on;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_refresh_token(&self, id: i32) -> Result<bool, Error> {
+        match self.store.refresh_tokens.write().await.get_mut(&id) {
+            Some(token) if token.revoked_on.is_none() => {
+                token.revoked_on = Some(Utc::now().naive_utc());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_account_tokens(&self, account_id: AccountId) -> Result<bool, Error> {
+        let now = Utc::now().naive_utc();
+        for token in self.store.refresh_tokens.write().await.values_mut() {
+            if token.account_id == account_id && token.revoked_on.is_none() {
+                token.revoked_on = Some(now);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn is_session_active(&self, session_id: i32) -> Result<bool, Error> {
+        match self.store.refresh_tokens.read().await.get(&session_id) {
+            Some(token) => Ok(token.is_active(Utc::now().naive_utc())),
+            None => Ok(false),
+        }
+    }
+
+    async fn add_api_key(&self, api_key: NewApiKey) -> Result<ApiKey, Error> {
+        let id = ApiKeyId(Self::next_id(&self.store.api_key_index).await);
+        let key_hash = api_key.key_hash;
+        let api_key = ApiKey {
+            id,
+            account_id: api_key.account_id,
+            name: api_key.name,
+            hint: api_key.hint,
+            scopes: api_key.scopes,
+            created_on: Utc::now().naive_utc(),
+            expires_on: api_key.expires_on,
+            last_used_on: None,
+            revoked_on: None,
+        };
+        self.store.api_keys.write().await.insert(key_hash, api_key.clone());
+        Ok(api_key)
+    }
+
+    async fn get_api_keys(&self, account_id: AccountId) -> Result<Vec<ApiKey>, Error> {
+        let mut api_keys: Vec<ApiKey> = self
+            .store
+            .api_keys
+            .read()
+            .await
+            .values()
+            .filter(|api_key| api_key.account_id == account_id)
+            .cloned()
+            .collect();
+        api_keys.sort_by_key(|api_key| std::cmp::Reverse(api_key.id.0));
+        Ok(api_keys)
+    }
+
+    async fn get_api_key(&self, key_hash: String) -> Result<ApiKey, Error> {
+        match self.store.api_keys.read().await.get(&key_hash) {
+            Some(api_key) => Ok(api_key.clone()),
+            None => Err(Error::NotFound),
+        }
+    }
+
+    async fn touch_api_key(&self, id: ApiKeyId) -> Result<bool, Error> {
+        match self.store.api_keys.write().await.values_mut().find(|api_key| api_key.id == id) {
+            Some(api_key) => {
+                api_key.last_used_on = Some(Utc::now().naive_utc());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn revoke_api_key(&self, account_id: AccountId, id: ApiKeyId) -> Result<bool, Error> {
+        match self
+            .store
+            .api_keys
+            .write()
+            .await
+            .values_mut()
+            .find(|api_key| api_key.id == id && api_key.account_id == account_id)
+        {
+            Some(api_key) if api_key.revoked_on.is_none() => {
+                api_key.revoked_on = Some(Utc::now().naive_utc());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn add_oidc_login(&self, login: OidcLogin) -> Result<bool, Error> {
+        self.store.oidc_logins.write().await.insert(login.state_hash.clone(), login);
+        Ok(true)
+    }
+
+    async fn take_oidc_login(&self, state_hash: String) -> Result<OidcLogin, Error> {
+        match self.store.oidc_logins.write().await.remove(&state_hash) {
+            Some(login) => Ok(login),
+            None => Err(Error::NotFound),
+        }
+    }
+}
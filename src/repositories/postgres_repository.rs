@@ -1,15 +1,28 @@
 use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
 use sqlx::{
-    postgres::{PgPool, PgPoolOptions, PgRow},
-    Row,
+    postgres::{PgConnection, PgPool, PgPoolOptions, PgRow},
+    Postgres, QueryBuilder, Row,
 };
 
 use crate::errors::Error;
 use crate::repositories::repository::{RepositoryPort};
 use crate::types::{
-    account::{Account, AccountId},
+    account::{Account, AccountId, ProfileUpdate, Role},
     answer::{Answer, AnswerId, NewAnswer},
-    question::{NewQuestion, Question, QuestionId},
+    api_key::{ApiKey, ApiKeyId, NewApiKey, Scope},
+    comment::{Comment, CommentId, CommentTarget, NewComment},
+    login_attempt::{LoginFailures, LoginKey},
+    moderation::{Actor, ModerationAction, ModerationEntry},
+    oidc::OidcLogin,
+    pagination::{Cursor, Page, Pagination},
+    prompt::KnowledgeSource,
+    question::{NewQuestion, Question, QuestionId, QuestionQuery, QuestionSort, QuestionStatus},
+    search::SearchResult,
+    tag::{TagCount, TagMatch},
+    token::{AccountToken, AccountTokenPurpose, NewAccountToken, NewRefreshToken, RefreshToken},
+    totp::Totp,
+    vote::VoteDirection,
 };
 
 #[derive(Debug, Clone)]
@@ -31,26 +44,284 @@ impl PostgresRepository {
     }
 }
 
+/// Status a question falls back to when it is (re)opened or loses its accepted answer
+const DERIVED_STATUS: &str = "CASE
+        WHEN accepted_answer IS NOT NULL THEN 'resolved'
+        WHEN EXISTS (SELECT 1 FROM answers WHERE answers.corresponding_question = questions.id) THEN 'answered'
+        ELSE 'open'
+    END";
+
+fn database_error(error: sqlx::Error) -> Error {
+    tracing::event!(tracing::Level::ERROR, "{:?}", error);
+    Error::DatabaseQueryError(error)
+}
+
+/// Number of answers of the question in the current row
+const ANSWER_COUNT: &str = "(SELECT COUNT(*) FROM answers WHERE answers.corresponding_question = questions.id)";
+
+/// Time of the newest answer, or of the question itself when it has none
+const LAST_ACTIVITY: &str = "GREATEST(created_on, \
+    (SELECT MAX(answers.created_on) FROM answers WHERE answers.corresponding_question = questions.id))";
+
+/// Appends the `WHERE` conditions shared by the listing and its count
+fn push_question_filters(query: &mut QueryBuilder<'_, Postgres>, filters: &QuestionQuery) {
+    if let Some(status) = filters.status {
+        query.push(" AND status = ").push_bind(status.as_str());
+    }
+    if let Some(filter) = &filters.tags {
+        // Both operators are served by the GIN index on `tags`
+        let operator = match filter.mode {
+            TagMatch::Any => " AND tags && ",
+            TagMatch::All => " AND tags @> ",
+        };
+        query.push(operator).push_bind(filter.tags.clone());
+    }
+    if let Some(author) = &filters.author {
+        query.push(" AND account_id = ").push_bind(author.0);
+    }
+    if let Some(after) = filters.created_after {
+        query.push(" AND created_on > ").push_bind(after);
+    }
+    if let Some(before) = filters.created_before {
+        query.push(" AND created_on < ").push_bind(before);
+    }
+    match filters.has_answers {
+        Some(true) => query.push(" AND EXISTS (SELECT 1 FROM answers WHERE answers.corresponding_question = questions.id)"),
+        Some(false) => query.push(" AND NOT EXISTS (SELECT 1 FROM answers WHERE answers.corresponding_question = questions.id)"),
+        None => query,
+    };
+}
+
+/// The account a row has to belong to, `None` when a moderator may change anyone's
+fn author_filter(actor: &Actor) -> Option<i32> {
+    match actor {
+        Actor::Author(account_id) => Some(account_id.0),
+        Actor::Moderator(_) => None,
+    }
+}
+
+/// Adds an entry to the moderation log when a moderator changed someone else's content
+async fn record_moderation(
+    tx: &mut PgConnection,
+    actor: &Actor,
+    action: ModerationAction,
+    target_id: i32,
+    author_id: Option<AccountId>,
+) -> Result<(), Error> {
+    if let Actor::Moderator(moderator_id) = actor {
+        sqlx::query(
+            "INSERT INTO moderation_log (moderator_id, action, target_id, author_id) VALUES ($1, $2, $3, $4)",
+        )
+            .bind(moderator_id.0)
+            .bind(action.as_str())
+            .bind(target_id)
+            .bind(author_id.map(|a| a.0))
+            .execute(tx)
+            .await
+            .map_err(database_error)?;
+    }
+    Ok(())
+}
+
+fn account_from_row(row: PgRow) -> Account {
+    Account {
+        id: Some(AccountId(row.get("id"))),
+        email: row.get("email"),
+        password: row.get("password"),
+        role: row.get::<String, _>("role").parse().unwrap_or_default(),
+        email_verified: row.get("email_verified"),
+        display_name: row.get("display_name"),
+        avatar_url: row.get("avatar_url"),
+        bio: row.get("bio"),
+        created_on: row.get("created_on"),
+    }
+}
+
+fn refresh_token_from_row(row: PgRow) -> RefreshToken {
+    RefreshToken {
+        id: row.get("id"),
+        account_id: AccountId(row.get("account_id")),
+        token_hash: row.get("token_hash"),
+        expires_on: row.get("expires_on"),
+        revoked_on: row.get("revoked_on"),
+        mfa: row.get("mfa"),
+    }
+}
+
+fn api_key_from_row(row: PgRow) -> ApiKey {
+    let scopes: Vec<String> = row.get("scopes");
+    ApiKey {
+        id: ApiKeyId(row.get("id")),
+        account_id: AccountId(row.get("account_id")),
+        name: row.get("name"),
+        hint: row.get("hint"),
+        // Scopes that no longer exist are dropped
+        scopes: scopes.iter().filter_map(|scope| scope.parse().ok()).collect(),
+        created_on: row.get("created_on"),
+        expires_on: row.get("expires_on"),
+        last_used_on: row.get("last_used_on"),
+        revoked_on: row.get("revoked_on"),
+    }
+}
+
+fn login_failures_from_row(row: PgRow) -> LoginFailures {
+    LoginFailures {
+        key: row.get("key"),
+        failures: row.get("failures"),
+        last_failure_on: row.get("last_failure_on"),
+        locked_until: row.get("locked_until"),
+    }
+}
+
+fn question_from_row(row: PgRow) -> Question {
+    Question {
+        id: QuestionId(row.get("id")),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        account_id: Some(AccountId(row.get("account_id"))),
+        created_on: Some(row.get("created_on")),
+        score: row.get("score"),
+        status: row.get::<String, _>("status").parse().unwrap_or_default(),
+        accepted_answer_id: row.get::<Option<i32>, _>("accepted_answer").map(AnswerId),
+    }
+}
+
+fn answer_from_row(row: PgRow) -> Answer {
+    Answer {
+        id: AnswerId(row.get("id")),
+        content: row.get("content"),
+        question_id: QuestionId(row.get("corresponding_question")),
+        account_id: Some(AccountId(row.get("account_id"))),
+        created_on: Some(row.get("created_on")),
+        score: row.get("score"),
+        prompt_version: row.get("prompt_version"),
+    }
+}
+
+fn comment_from_row(row: PgRow) -> Comment {
+    Comment {
+        id: CommentId(row.get("id")),
+        content: row.get("content"),
+        question_id: row.get::<Option<i32>, _>("question_id").map(QuestionId),
+        answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+        account_id: Some(AccountId(row.get("account_id"))),
+        created_on: Some(row.get("created_on")),
+    }
+}
+
+/// `ts_headline` options of the search snippets
+const SEARCH_HEADLINE: &str = "StartSel=<mark>, StopSel=</mark>, MaxWords=35, MinWords=15";
+
+/// Questions and answers matching `tsquery`, which may use `$1`. Binds the
+/// limit as `$2` and [`SEARCH_HEADLINE`] as `$3`.
+fn search_sql(tsquery: &str) -> String {
+    format!(
+        "WITH search_query AS (SELECT {tsquery} AS query)
+        SELECT * FROM (
+            SELECT q.id AS question_id, NULL::integer AS answer_id, q.title,
+                ts_headline('english', q.content, query, $3) AS snippet,
+                ts_rank(q.search, query) AS rank
+            FROM questions q, search_query
+            WHERE q.search @@ query
+            UNION ALL
+            SELECT a.corresponding_question, a.id, q.title,
+                ts_headline('english', a.content, query, $3),
+                ts_rank(a.search, query)
+            FROM answers a JOIN questions q ON q.id = a.corresponding_question, search_query
+            WHERE a.search @@ query
+        ) results ORDER BY rank DESC, question_id LIMIT $2",
+        tsquery = tsquery
+    )
+}
+
+fn search_result_from_row(row: PgRow) -> SearchResult {
+    SearchResult {
+        question_id: QuestionId(row.get("question_id")),
+        answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+        title: row.get("title"),
+        snippet: row.get("snippet"),
+        rank: row.get("rank"),
+    }
+}
+
+/// Splits a comment target into the `question_id` and `answer_id` columns
+fn comment_target_columns(target: &CommentTarget) -> (Option<i32>, Option<i32>) {
+    match target {
+        CommentTarget::Question(id) => (Some(id.0), None),
+        CommentTarget::Answer(id) => (None, Some(id.0)),
+    }
+}
+
 #[async_trait]
 impl RepositoryPort for PostgresRepository {
     async fn get_questions(
         &self,
-        limit: Option<i32>,
-        offset: i32,
-    ) -> Result<Vec<Question>, Error> {
-        match sqlx::query("SELECT * from questions LIMIT $1 OFFSET $2")
-            .bind(limit)
-            .bind(offset)
-            .map(|row: PgRow| Question {
-                id: QuestionId(row.get("id")),
-                title: row.get("title"),
-                content: row.get("content"),
-                tags: row.get("tags"),
-            })
+        query: QuestionQuery,
+    ) -> Result<Page<Question>, Error> {
+        let pagination = &query.pagination;
+        let keyset = query.sort.is_keyset();
+
+        let mut count = QueryBuilder::<Postgres>::new("SELECT COUNT(*) from questions WHERE TRUE");
+        push_question_filters(&mut count, &query);
+        let total: i64 = count
+            .build()
+            .map(|row: PgRow| row.get(0))
+            .fetch_one(&self.connection)
+            .await
+            .map_err(database_error)?;
+
+        let mut select = QueryBuilder::<Postgres>::new("SELECT * from questions WHERE TRUE");
+        push_question_filters(&mut select, &query);
+        let backwards = matches!(pagination.cursor, Some(Cursor::Before(..)));
+        // Keyset orderings read backwards from a `Before` cursor, and `newest` runs
+        // the other way round to begin with
+        let descending = backwards != (query.sort == QuestionSort::Newest);
+        match (&pagination.cursor, keyset) {
+            (Some(Cursor::After(created_on, id) | Cursor::Before(created_on, id)), true) => {
+                let operator = if descending { " AND (created_on, id) < (" } else { " AND (created_on, id) > (" };
+                select.push(operator).push_bind(*created_on).push(", ").push_bind(*id).push(")");
+            }
+            (Some(Cursor::After(..) | Cursor::Before(..)), false) => {
+                return Err(Error::InvalidParameter("cursor".to_string()))
+            }
+            _ => {}
+        }
+        match query.sort {
+            QuestionSort::Newest | QuestionSort::Oldest if descending => {
+                select.push(" ORDER BY created_on DESC, id DESC")
+            }
+            QuestionSort::Newest | QuestionSort::Oldest => select.push(" ORDER BY created_on, id"),
+            QuestionSort::Score => select.push(" ORDER BY score DESC, created_on, id"),
+            QuestionSort::Activity => select
+                .push(" ORDER BY ")
+                .push(LAST_ACTIVITY)
+                .push(" DESC, created_on DESC, id DESC"),
+            QuestionSort::Unanswered => select
+                .push(" ORDER BY ")
+                .push(ANSWER_COUNT)
+                .push(", created_on, id"),
+        };
+        // One extra row tells whether there is another page
+        select.push(" LIMIT ").push_bind(pagination.limit + 1);
+        if let Some(Cursor::Offset(offset)) = pagination.cursor {
+            select.push(" OFFSET ").push_bind(offset);
+        }
+
+        match select
+            .build()
+            .map(question_from_row)
             .fetch_all(&self.connection)
             .await
         {
-            Ok(questions) => Ok(questions),
+            Ok(mut questions) => {
+                if backwards {
+                    questions.reverse();
+                }
+                Ok(Page::from_fetched(questions, total, pagination, keyset, |q| {
+                    (q.created_on.unwrap_or_default(), q.id.0)
+                }))
+            }
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -63,16 +334,12 @@ impl RepositoryPort for PostgresRepository {
     ) -> Result<Question, Error> {
         match sqlx::query("SELECT * from questions where id = $1")
             .bind(question_id)
-            .map(|row: PgRow| Question {
-                id: QuestionId(row.get("id")),
-                title: row.get("title"),
-                content: row.get("content"),
-                tags: row.get("tags"),
-            })
+            .map(question_from_row)
             .fetch_optional(&self.connection)
             .await
         {
-            Ok(question) => Ok(question.unwrap()),
+            Ok(Some(question)) => Ok(question),
+            Ok(None) => Err(Error::NotFound),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -102,17 +369,12 @@ impl RepositoryPort for PostgresRepository {
         new_question: NewQuestion,
         account_id: AccountId,
     ) -> Result<Question, Error> {
-        match sqlx::query("INSERT INTO questions (title, content, tags, account_id) VALUES ($1, $2, $3, $4) RETURNING id, title, content, tags")
+        match sqlx::query("INSERT INTO questions (title, content, tags, account_id) VALUES ($1, $2, $3, $4) RETURNING id, title, content, tags, account_id, created_on, score, status, accepted_answer")
             .bind(new_question.title)
             .bind(new_question.content)
             .bind(new_question.tags)
             .bind(account_id.0)
-            .map(|row: PgRow| Question {
-                id: QuestionId(row.get("id")),
-                title: row.get("title"),
-                content: row.get("content"),
-                tags: row.get("tags"),
-            })
+            .map(question_from_row)
             .fetch_one(&self.connection)
             .await {
             Ok(question) => Ok(question),
@@ -126,45 +388,145 @@ impl RepositoryPort for PostgresRepository {
         &self,
         question: Question,
         id: i32,
-        account_id: AccountId,
+        actor: Actor,
     ) -> Result<Question, Error> {
-        match sqlx::query(
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let updated = sqlx::query(
             "UPDATE questions SET title = $1, content = $2, tags = $3
-        WHERE id = $4 AND account_id = $5
-        RETURNING id, title, content, tags",
+        WHERE id = $4 AND ($5::integer IS NULL OR account_id = $5)
+        RETURNING id, title, content, tags, account_id, created_on, score, status, accepted_answer",
         )
             .bind(question.title)
             .bind(question.content)
             .bind(question.tags)
+            .bind(id)
+            .bind(author_filter(&actor))
+            .map(question_from_row)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(database_error)?
+            .ok_or(Error::NotFound)?;
+
+        record_moderation(&mut tx, &actor, ModerationAction::UpdateQuestion, id, updated.account_id.clone()).await?;
+        tx.commit().await.map_err(database_error)?;
+        Ok(updated)
+    }
+    async fn delete_question(&self, id: i32, actor: Actor) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let author_id: Option<i32> = sqlx::query(
+            "DELETE FROM questions WHERE id = $1 AND ($2::integer IS NULL OR account_id = $2) RETURNING account_id",
+        )
+            .bind(id)
+            .bind(author_filter(&actor))
+            .map(|row: PgRow| row.get("account_id"))
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        if let Some(author_id) = author_id {
+            record_moderation(&mut tx, &actor, ModerationAction::DeleteQuestion, id, Some(AccountId(author_id))).await?;
+        }
+        tx.commit().await.map_err(database_error)?;
+        Ok(true)
+    }
+    async fn vote_question(
+        &self,
+        id: i32,
+        account_id: AccountId,
+        direction: VoteDirection,
+    ) -> Result<Question, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        // Lock the question so concurrent votes recompute the score one after another
+        let exists = sqlx::query("SELECT id from questions where id = $1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        if exists.is_none() {
+            return Err(Error::NotFound);
+        }
+
+        sqlx::query(
+            "INSERT INTO question_votes (question_id, account_id, value) VALUES ($1, $2, $3)
+        ON CONFLICT (question_id, account_id) DO UPDATE SET value = EXCLUDED.value",
+        )
             .bind(id)
             .bind(account_id.0)
-            .map(|row: PgRow| Question {
-                id: QuestionId(row.get("id")),
-                title: row.get("title"),
-                content: row.get("content"),
-                tags: row.get("tags"),
-            })
-            .fetch_one(&self.connection)
+            .bind(direction.value() as i16)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        let question = sqlx::query(
+            "UPDATE questions SET score = (SELECT COALESCE(SUM(value), 0) FROM question_votes WHERE question_id = $1)
+        WHERE id = $1
+        RETURNING id, title, content, tags, account_id, created_on, score, status, accepted_answer",
+        )
+            .bind(id)
+            .map(question_from_row)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+        Ok(question)
+    }
+    async fn accept_answer(
+        &self,
+        question_id: i32,
+        answer_id: i32,
+        account_id: AccountId,
+    ) -> Result<Question, Error> {
+        match sqlx::query(
+            "UPDATE questions SET accepted_answer = $2, status = 'resolved'
+        WHERE id = $1 AND account_id = $3
+        AND EXISTS (SELECT 1 FROM answers WHERE id = $2 AND corresponding_question = $1)
+        RETURNING id, title, content, tags, account_id, created_on, score, status, accepted_answer",
+        )
+            .bind(question_id)
+            .bind(answer_id)
+            .bind(account_id.0)
+            .map(question_from_row)
+            .fetch_optional(&self.connection)
             .await
         {
-            Ok(question) => Ok(question),
+            Ok(Some(question)) => Ok(question),
+            Ok(None) => Err(Error::NotFound),
             Err(error) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", error);
                 Err(Error::DatabaseQueryError(error))
             }
         }
     }
-    async fn delete_question(&self, id: i32, account_id: AccountId) -> Result<bool, Error> {
-        match sqlx::query("DELETE FROM questions WHERE id = $1 AND account_id = $2")
+    async fn update_question_status(
+        &self,
+        id: i32,
+        status: QuestionStatus,
+        account_id: AccountId,
+    ) -> Result<Question, Error> {
+        let status = match status {
+            QuestionStatus::Closed => "'closed'",
+            _ => DERIVED_STATUS,
+        };
+        match sqlx::query(&format!(
+            "UPDATE questions SET status = {}
+        WHERE id = $1 AND account_id = $2
+        RETURNING id, title, content, tags, account_id, created_on, score, status, accepted_answer",
+            status
+        ))
             .bind(id)
             .bind(account_id.0)
-            .execute(&self.connection)
+            .map(question_from_row)
+            .fetch_one(&self.connection)
             .await
         {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
+            Ok(question) => Ok(question),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
             }
         }
     }
@@ -173,22 +535,21 @@ impl RepositoryPort for PostgresRepository {
         new_answer: NewAnswer,
         account_id: AccountId,
     ) -> Result<Answer, Error> {
-        match sqlx::query(
-            "INSERT INTO answers (content, corresponding_question, account_id) VALUES ($1, $2, $3) RETURNING id, content, corresponding_question",
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        let question_id = new_answer.question_id.0;
+
+        let answer = match sqlx::query(
+            "INSERT INTO answers (content, corresponding_question, account_id, prompt_version) VALUES ($1, $2, $3, $4) RETURNING id, content, corresponding_question, account_id, created_on, score, prompt_version",
         )
             .bind(new_answer.content)
-            .bind(new_answer.question_id.0)
+            .bind(question_id)
             .bind(account_id.0)
-            .map(|row: PgRow|
-                Answer {
-                    id: AnswerId(row.get("id")),
-                    content: row.get("content"),
-                    question_id: QuestionId(row.get("corresponding_question")),
-                })
-            .fetch_one(&self.connection)
+            .bind(new_answer.prompt_version)
+            .map(answer_from_row)
+            .fetch_one(&mut *tx)
             .await
         {
-            Ok(answer) => Ok(answer),
+            Ok(answer) => answer,
             Err(error) => {
                 tracing::event!(
                     tracing::Level::ERROR,
@@ -202,14 +563,336 @@ impl RepositoryPort for PostgresRepository {
                     db_message = error.as_database_error().unwrap().message(),
                     constraint = error.as_database_error().unwrap().constraint().unwrap()
                 );
+                return Err(Error::DatabaseQueryError(error));
+            }
+        };
+
+        sqlx::query("UPDATE questions SET status = 'answered' WHERE id = $1 AND status = 'open'")
+            .bind(question_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+        Ok(answer)
+    }
+    async fn get_answers(
+        &self,
+        question_id: i32,
+    ) -> Result<Vec<Answer>, Error> {
+        match sqlx::query("SELECT * from answers where corresponding_question = $1 ORDER BY created_on, id")
+            .bind(question_id)
+            .map(answer_from_row)
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(answers) => Ok(answers),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+    async fn get_answer(
+        &self,
+        answer_id: i32,
+    ) -> Result<Answer, Error> {
+        match sqlx::query("SELECT * from answers where id = $1")
+            .bind(answer_id)
+            .map(answer_from_row)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(Some(answer)) => Ok(answer),
+            Ok(None) => Err(Error::NotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+    async fn is_answer_owner(
+        &self,
+        answer_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        match sqlx::query("SELECT * from answers where id = $1 and account_id = $2")
+            .bind(answer_id)
+            .bind(account_id.0)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(answer) => Ok(answer.is_some()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+    async fn update_answer(
+        &self,
+        answer: Answer,
+        id: i32,
+        actor: Actor,
+    ) -> Result<Answer, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let updated = sqlx::query(
+            "UPDATE answers SET content = $1
+        WHERE id = $2 AND ($3::integer IS NULL OR account_id = $3)
+        RETURNING id, content, corresponding_question, account_id, created_on, score, prompt_version",
+        )
+            .bind(answer.content)
+            .bind(id)
+            .bind(author_filter(&actor))
+            .map(answer_from_row)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(database_error)?
+            .ok_or(Error::NotFound)?;
+
+        record_moderation(&mut tx, &actor, ModerationAction::UpdateAnswer, id, updated.account_id.clone()).await?;
+        tx.commit().await.map_err(database_error)?;
+        Ok(updated)
+    }
+    async fn delete_answer(&self, id: i32, actor: Actor) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let deleted: Option<(i32, i32)> = sqlx::query(
+            "DELETE FROM answers WHERE id = $1 AND ($2::integer IS NULL OR account_id = $2)
+        RETURNING corresponding_question, account_id",
+        )
+            .bind(id)
+            .bind(author_filter(&actor))
+            .map(|row: PgRow| (row.get("corresponding_question"), row.get("account_id")))
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        // The question may have lost its only or its accepted answer
+        if let Some((question_id, author_id)) = deleted {
+            sqlx::query(&format!(
+                "UPDATE questions SET status = {} WHERE id = $1 AND status <> 'closed'",
+                DERIVED_STATUS
+            ))
+                .bind(question_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(database_error)?;
+            record_moderation(&mut tx, &actor, ModerationAction::DeleteAnswer, id, Some(AccountId(author_id))).await?;
+        }
+
+        tx.commit().await.map_err(database_error)?;
+        Ok(true)
+    }
+    async fn vote_answer(
+        &self,
+        id: i32,
+        account_id: AccountId,
+        direction: VoteDirection,
+    ) -> Result<Answer, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        // Lock the answer so concurrent votes recompute the score one after another
+        let exists = sqlx::query("SELECT id from answers where id = $1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        if exists.is_none() {
+            return Err(Error::NotFound);
+        }
+
+        sqlx::query(
+            "INSERT INTO answer_votes (answer_id, account_id, value) VALUES ($1, $2, $3)
+        ON CONFLICT (answer_id, account_id) DO UPDATE SET value = EXCLUDED.value",
+        )
+            .bind(id)
+            .bind(account_id.0)
+            .bind(direction.value() as i16)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        let answer = sqlx::query(
+            "UPDATE answers SET score = (SELECT COALESCE(SUM(value), 0) FROM answer_votes WHERE answer_id = $1)
+        WHERE id = $1
+        RETURNING id, content, corresponding_question, account_id, created_on, score, prompt_version",
+        )
+            .bind(id)
+            .map(answer_from_row)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+        Ok(answer)
+    }
+    async fn add_comment(
+        &self,
+        target: CommentTarget,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error> {
+        let (question_id, answer_id) = comment_target_columns(&target);
+        match sqlx::query(
+            "INSERT INTO comments (content, question_id, answer_id, account_id) VALUES ($1, $2, $3, $4)
+        RETURNING id, content, question_id, answer_id, account_id, created_on",
+        )
+            .bind(new_comment.content)
+            .bind(question_id)
+            .bind(answer_id)
+            .bind(account_id.0)
+            .map(comment_from_row)
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(comment) => Ok(comment),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
                 Err(Error::DatabaseQueryError(error))
             }
         }
     }
+    async fn get_comments(&self, target: CommentTarget) -> Result<Vec<Comment>, Error> {
+        let (question_id, answer_id) = comment_target_columns(&target);
+        match sqlx::query(
+            "SELECT * from comments where question_id = $1 OR answer_id = $2 ORDER BY created_on, id",
+        )
+            .bind(question_id)
+            .bind(answer_id)
+            .map(comment_from_row)
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(comments) => Ok(comments),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+    async fn is_comment_owner(
+        &self,
+        comment_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        match sqlx::query("SELECT * from comments where id = $1 and account_id = $2")
+            .bind(comment_id)
+            .bind(account_id.0)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(comment) => Ok(comment.is_some()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+    async fn delete_comment(
+        &self,
+        target: CommentTarget,
+        id: i32,
+        account_id: AccountId,
+    ) -> Result<bool, Error> {
+        let (question_id, answer_id) = comment_target_columns(&target);
+        match sqlx::query(
+            "DELETE FROM comments WHERE id = $1 AND account_id = $2 AND (question_id = $3 OR answer_id = $4)",
+        )
+            .bind(id)
+            .bind(account_id.0)
+            .bind(question_id)
+            .bind(answer_id)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(result) if result.rows_affected() > 0 => Ok(true),
+            Ok(_) => Err(Error::NotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+    async fn get_tags(&self) -> Result<Vec<TagCount>, Error> {
+        match sqlx::query(
+            "SELECT tag, COUNT(DISTINCT id) AS count FROM questions, unnest(tags) AS tag
+        GROUP BY tag ORDER BY count DESC, tag",
+        )
+            .map(|row: PgRow| TagCount {
+                tag: row.get("tag"),
+                count: row.get("count"),
+            })
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(tags) => Ok(tags),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+    async fn search(&self, query: String, limit: i64) -> Result<Vec<SearchResult>, Error> {
+        sqlx::query(&search_sql("websearch_to_tsquery('english', $1)"))
+            .bind(query)
+            .bind(limit)
+            .bind(SEARCH_HEADLINE)
+            .map(search_result_from_row)
+            .fetch_all(&self.connection)
+            .await
+            .map_err(database_error)
+    }
+    async fn search_any(&self, terms: &[String], limit: i64) -> Result<Vec<SearchResult>, Error> {
+        // The lexemes of every term joined with `|`, stop words drop out and
+        // leave NULL when nothing is left, which matches no row
+        sqlx::query(&search_sql(
+            "(SELECT string_agg('(' || plainto_tsquery('english', term)::text || ')', ' | ')::tsquery
+                FROM unnest($1::text[]) term
+                WHERE plainto_tsquery('english', term)::text <> '')",
+        ))
+            .bind(terms)
+            .bind(limit)
+            .bind(SEARCH_HEADLINE)
+            .map(search_result_from_row)
+            .fetch_all(&self.connection)
+            .await
+            .map_err(database_error)
+    }
+    async fn get_knowledge_sources(&self, question_ids: &[QuestionId]) -> Result<Vec<KnowledgeSource>, Error> {
+        let ids: Vec<i32> = question_ids.iter().map(|id| id.0).collect();
+        sqlx::query(
+            "SELECT * FROM (
+            SELECT DISTINCT ON (q.id) q.id, q.title, a.content
+            FROM questions q JOIN answers a ON a.corresponding_question = q.id
+            WHERE q.id = ANY($1) AND q.status IN ('answered', 'resolved')
+                AND (a.id = q.accepted_answer OR (q.accepted_answer IS NULL AND a.prompt_version IS NULL))
+            ORDER BY q.id, a.score DESC, a.created_on DESC, a.id DESC
+        ) sources ORDER BY array_position($1, id)",
+        )
+            .bind(ids)
+            .map(|row: PgRow| KnowledgeSource {
+                question_id: QuestionId(row.get("id")),
+                title: row.get("title"),
+                answer: row.get("content"),
+            })
+            .fetch_all(&self.connection)
+            .await
+            .map_err(database_error)
+    }
     async fn add_account(&self, account: Account) -> Result<bool, Error> {
-        match sqlx::query("INSERT INTO accounts (email, password) VALUES ($1, $2)")
+        match sqlx::query(
+            "INSERT INTO accounts (email, password, role, email_verified, display_name, avatar_url, bio)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
             .bind(account.email)
             .bind(account.password)
+            .bind(account.role.as_str())
+            .bind(account.email_verified)
+            .bind(account.display_name)
+            .bind(account.avatar_url)
+            .bind(account.bio)
             .execute(&self.connection)
             .await
         {
@@ -234,19 +917,497 @@ impl RepositoryPort for PostgresRepository {
     async fn get_account(&self, email: String) -> Result<Account, Error> {
         match sqlx::query("SELECT * from accounts where email = $1")
             .bind(email)
-            .map(|row: PgRow| Account {
-                id: Some(AccountId(row.get("id"))),
-                email: row.get("email"),
-                password: row.get("password"),
-            })
-            .fetch_one(&self.connection)
+            .map(account_from_row)
+            .fetch_optional(&self.connection)
             .await
         {
-            Ok(account) => Ok(account),
+            Ok(account) => account.ok_or(Error::NotFound),
             Err(error) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", error);
                 Err(Error::DatabaseQueryError(error))
             }
         }
     }
+    async fn get_account_by_id(&self, account_id: AccountId) -> Result<Account, Error> {
+        sqlx::query("SELECT * from accounts where id = $1")
+            .bind(account_id.0)
+            .map(account_from_row)
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(database_error)?
+            .ok_or(Error::NotFound)
+    }
+    async fn update_account_role(&self, account_id: AccountId, role: Role) -> Result<bool, Error> {
+        let updated = sqlx::query("UPDATE accounts SET role = $1 WHERE id = $2")
+            .bind(role.as_str())
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        if updated.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(true)
+    }
+    async fn update_account_password(&self, account_id: AccountId, password: String) -> Result<bool, Error> {
+        let updated = sqlx::query("UPDATE accounts SET password = $1 WHERE id = $2")
+            .bind(password)
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        if updated.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(true)
+    }
+    async fn verify_account_email(&self, account_id: AccountId) -> Result<bool, Error> {
+        let updated = sqlx::query("UPDATE accounts SET email_verified = TRUE WHERE id = $1")
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        if updated.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(true)
+    }
+    async fn update_account_profile(&self, account_id: AccountId, profile: ProfileUpdate) -> Result<Account, Error> {
+        sqlx::query("UPDATE accounts SET display_name = $1, avatar_url = $2, bio = $3 WHERE id = $4 RETURNING *")
+            .bind(profile.display_name)
+            .bind(profile.avatar_url)
+            .bind(profile.bio)
+            .bind(account_id.0)
+            .map(account_from_row)
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(database_error)?
+            .ok_or(Error::NotFound)
+    }
+    async fn delete_account(&self, account_id: AccountId) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let email: Option<String> = sqlx::query("DELETE FROM accounts WHERE id = $1 RETURNING email")
+            .bind(account_id.0)
+            .map(|row: PgRow| row.get("email"))
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        let email = match email {
+            Some(email) => email,
+            None => return Ok(false),
+        };
+
+        for table in ["refresh_tokens", "account_tokens", "account_totp", "recovery_codes", "api_keys"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE account_id = $1", table))
+                .bind(account_id.0)
+                .execute(&mut *tx)
+                .await
+                .map_err(database_error)?;
+        }
+        sqlx::query("DELETE FROM login_failures WHERE key = $1")
+            .bind(LoginKey::Email(email).to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+        Ok(true)
+    }
+    async fn get_account_answers(&self, account_id: AccountId, limit: i64) -> Result<Vec<Answer>, Error> {
+        sqlx::query("SELECT * from answers where account_id = $1 ORDER BY created_on DESC, id DESC LIMIT $2")
+            .bind(account_id.0)
+            .bind(limit)
+            .map(answer_from_row)
+            .fetch_all(&self.connection)
+            .await
+            .map_err(database_error)
+    }
+    async fn add_account_token(&self, token: NewAccountToken) -> Result<AccountToken, Error> {
+        sqlx::query(
+            "INSERT INTO account_tokens (account_id, purpose, token_hash, expires_on) VALUES ($1, $2, $3, $4)
+        RETURNING id, account_id, purpose, token_hash, expires_on, used_on",
+        )
+            .bind(token.account_id.0)
+            .bind(token.purpose.as_str())
+            .bind(token.token_hash)
+            .bind(token.expires_on)
+            .try_map(|row: PgRow| {
+                Ok(AccountToken {
+                    id: row.get("id"),
+                    account_id: AccountId(row.get("account_id")),
+                    purpose: row
+                        .get::<String, _>("purpose")
+                        .parse()
+                        .map_err(|e: Error| sqlx::Error::Decode(e.to_string().into()))?,
+                    token_hash: row.get("token_hash"),
+                    expires_on: row.get("expires_on"),
+                    used_on: row.get("used_on"),
+                })
+            })
+            .fetch_one(&self.connection)
+            .await
+            .map_err(database_error)
+    }
+    async fn use_account_token(&self, purpose: AccountTokenPurpose, token_hash: String) -> Result<AccountId, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        // Only one of two concurrent requests with the same token matches the row
+        let account_id: i32 = sqlx::query(
+            "UPDATE account_tokens SET used_on = NOW()
+        WHERE token_hash = $1 AND purpose = $2 AND used_on IS NULL AND expires_on > $3
+        RETURNING account_id",
+        )
+            .bind(token_hash)
+            .bind(purpose.as_str())
+            .bind(Utc::now().naive_utc())
+            .map(|row: PgRow| row.get("account_id"))
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(database_error)?
+            .ok_or(Error::NotFound)?;
+
+        sqlx::query("UPDATE account_tokens SET used_on = NOW() WHERE account_id = $1 AND purpose = $2 AND used_on IS NULL")
+            .bind(account_id)
+            .bind(purpose.as_str())
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+        Ok(AccountId(account_id))
+    }
+    async fn get_moderation_log(&self, pagination: Pagination) -> Result<Page<ModerationEntry>, Error> {
+        let offset = match pagination.cursor {
+            None => 0,
+            Some(Cursor::Offset(offset)) => offset,
+            Some(_) => return Err(Error::InvalidParameter("cursor".to_string())),
+        };
+
+        let total: i64 = sqlx::query("SELECT COUNT(*) from moderation_log")
+            .map(|row: PgRow| row.get(0))
+            .fetch_one(&self.connection)
+            .await
+            .map_err(database_error)?;
+
+        // One extra row tells whether there is another page
+        let entries = sqlx::query("SELECT * from moderation_log ORDER BY created_on DESC, id DESC LIMIT $1 OFFSET $2")
+            .bind(pagination.limit + 1)
+            .bind(offset)
+            .try_map(|row: PgRow| {
+                Ok(ModerationEntry {
+                    id: row.get("id"),
+                    moderator_id: AccountId(row.get("moderator_id")),
+                    action: row
+                        .get::<String, _>("action")
+                        .parse()
+                        .map_err(|e: Error| sqlx::Error::Decode(e.to_string().into()))?,
+                    target_id: row.get("target_id"),
+                    author_id: row.get::<Option<i32>, _>("author_id").map(AccountId),
+                    created_on: row.get("created_on"),
+                })
+            })
+            .fetch_all(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(Page::from_fetched(entries, total, &pagination, false, |e| (e.created_on, e.id)))
+    }
+    async fn get_totp(&self, account_id: AccountId) -> Result<Totp, Error> {
+        sqlx::query("SELECT * from account_totp where account_id = $1")
+            .bind(account_id.0)
+            .map(|row: PgRow| Totp {
+                account_id: AccountId(row.get("account_id")),
+                secret: row.get("secret"),
+                enabled_on: row.get("enabled_on"),
+                last_used_step: row.get("last_used_step"),
+            })
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(database_error)?
+            .ok_or(Error::NotFound)
+    }
+    async fn set_totp_secret(&self, account_id: AccountId, secret: String) -> Result<bool, Error> {
+        let stored = sqlx::query(
+            "INSERT INTO account_totp (account_id, secret) VALUES ($1, $2)
+        ON CONFLICT (account_id) DO UPDATE SET secret = EXCLUDED.secret, created_on = NOW(), last_used_step = NULL
+        WHERE account_totp.enabled_on IS NULL",
+        )
+            .bind(account_id.0)
+            .bind(secret)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(stored.rows_affected() == 1)
+    }
+    async fn enable_totp(&self, account_id: AccountId, step: i64, recovery_code_hashes: Vec<String>) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+
+        let enabled = sqlx::query(
+            "UPDATE account_totp SET enabled_on = NOW(), last_used_step = $1
+        WHERE account_id = $2 AND enabled_on IS NULL",
+        )
+            .bind(step)
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        if enabled.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query("DELETE FROM recovery_codes WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        sqlx::query("INSERT INTO recovery_codes (account_id, code_hash) SELECT $1, UNNEST($2::text[])")
+            .bind(account_id.0)
+            .bind(recovery_code_hashes)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+
+        tx.commit().await.map_err(database_error)?;
+        Ok(true)
+    }
+    async fn disable_totp(&self, account_id: AccountId) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(database_error)?;
+        sqlx::query("DELETE FROM recovery_codes WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        let deleted = sqlx::query("DELETE FROM account_totp WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(database_error)?;
+        tx.commit().await.map_err(database_error)?;
+        Ok(deleted.rows_affected() == 1)
+    }
+    async fn use_totp_step(&self, account_id: AccountId, step: i64) -> Result<bool, Error> {
+        // Only one of two concurrent logins with the same code matches the row
+        let used = sqlx::query(
+            "UPDATE account_totp SET last_used_step = $1
+        WHERE account_id = $2 AND enabled_on IS NOT NULL AND (last_used_step IS NULL OR last_used_step < $1)",
+        )
+            .bind(step)
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(used.rows_affected() == 1)
+    }
+    async fn use_recovery_code(&self, account_id: AccountId, code_hash: String) -> Result<bool, Error> {
+        let used = sqlx::query(
+            "UPDATE recovery_codes SET used_on = NOW()
+        WHERE id = (SELECT id FROM recovery_codes WHERE account_id = $1 AND code_hash = $2 AND used_on IS NULL LIMIT 1)
+        AND used_on IS NULL",
+        )
+            .bind(account_id.0)
+            .bind(code_hash)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(used.rows_affected() == 1)
+    }
+    async fn get_login_failures(&self, key: String) -> Result<Option<LoginFailures>, Error> {
+        sqlx::query("SELECT * from login_failures where key = $1")
+            .bind(key)
+            .map(login_failures_from_row)
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(database_error)
+    }
+    async fn add_login_failure(&self, key: String, window_start: NaiveDateTime) -> Result<LoginFailures, Error> {
+        sqlx::query(
+            "INSERT INTO login_failures (key, failures, last_failure_on) VALUES ($1, 1, $2)
+        ON CONFLICT (key) DO UPDATE SET last_failure_on = EXCLUDED.last_failure_on,
+            failures = CASE WHEN login_failures.last_failure_on < $3 THEN 1 ELSE login_failures.failures + 1 END
+        RETURNING key, failures, last_failure_on, locked_until",
+        )
+            .bind(key)
+            .bind(Utc::now().naive_utc())
+            .bind(window_start)
+            .map(login_failures_from_row)
+            .fetch_one(&self.connection)
+            .await
+            .map_err(database_error)
+    }
+    async fn lock_login(&self, key: String, until: NaiveDateTime) -> Result<bool, Error> {
+        let locked = sqlx::query("UPDATE login_failures SET locked_until = $1 WHERE key = $2")
+            .bind(until)
+            .bind(key)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(locked.rows_affected() == 1)
+    }
+    async fn clear_login_failures(&self, key: String) -> Result<bool, Error> {
+        let deleted = sqlx::query("DELETE FROM login_failures WHERE key = $1")
+            .bind(key)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(deleted.rows_affected() == 1)
+    }
+    async fn add_refresh_token(&self, token: NewRefreshToken) -> Result<RefreshToken, Error> {
+        sqlx::query(
+            "INSERT INTO refresh_tokens (account_id, token_hash, expires_on, mfa) VALUES ($1, $2, $3, $4)
+        RETURNING id, account_id, token_hash, expires_on, revoked_on, mfa",
+        )
+            .bind(token.account_id.0)
+            .bind(token.token_hash)
+            .bind(token.expires_on)
+            .bind(token.mfa)
+            .map(refresh_token_from_row)
+            .fetch_one(&self.connection)
+            .await
+            .map_err(database_error)
+    }
+    async fn get_refresh_token(&self, token_hash: String) -> Result<RefreshToken, Error> {
+        sqlx::query("SELECT * from refresh_tokens where token_hash = $1")
+            .bind(token_hash)
+            .map(refresh_token_from_row)
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(database_error)?
+            .ok_or(Error::NotFound)
+    }
+    async fn rotate_refresh_token(
+        &self,
+        id: i32,
+        token_hash: String,
+        new_token_hash: String,
+        expires_on: NaiveDateTime,
+    ) -> Result<bool, Error> {
+        // Only one of two concurrent refreshes with the same token matches the row
+        let rotated = sqlx::query(
+            "UPDATE refresh_tokens SET token_hash = $1, expires_on = $2
+        WHERE id = $3 AND token_hash = $4 AND revoked_on IS NULL AND expires_on > $5",
+        )
+            .bind(new_token_hash)
+            .bind(expires_on)
+            .bind(id)
+            .bind(token_hash)
+            .bind(Utc::now().naive_utc())
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(rotated.rows_affected() == 1)
+    }
+    async fn revoke_refresh_token(&self, id: i32) -> Result<bool, Error> {
+        let revoked = sqlx::query("UPDATE refresh_tokens SET revoked_on = NOW() WHERE id = $1 AND revoked_on IS NULL")
+            .bind(id)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(revoked.rows_affected() == 1)
+    }
+    async fn revoke_account_tokens(&self, account_id: AccountId) -> Result<bool, Error> {
+        sqlx::query("UPDATE refresh_tokens SET revoked_on = NOW() WHERE account_id = $1 AND revoked_on IS NULL")
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(true)
+    }
+    async fn is_session_active(&self, session_id: i32) -> Result<bool, Error> {
+        let active = sqlx::query(
+            "SELECT 1 from refresh_tokens where id = $1 AND revoked_on IS NULL AND expires_on > $2",
+        )
+            .bind(session_id)
+            // `expires_on` is written in UTC, whatever the time zone of the database
+            .bind(Utc::now().naive_utc())
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(active.is_some())
+    }
+    async fn add_api_key(&self, api_key: NewApiKey) -> Result<ApiKey, Error> {
+        let scopes: Vec<&str> = api_key.scopes.iter().map(Scope::as_str).collect();
+        sqlx::query(
+            "INSERT INTO api_keys (account_id, name, hint, key_hash, scopes, expires_on) VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *",
+        )
+            .bind(api_key.account_id.0)
+            .bind(api_key.name)
+            .bind(api_key.hint)
+            .bind(api_key.key_hash)
+            .bind(scopes)
+            .bind(api_key.expires_on)
+            .map(api_key_from_row)
+            .fetch_one(&self.connection)
+            .await
+            .map_err(database_error)
+    }
+    async fn get_api_keys(&self, account_id: AccountId) -> Result<Vec<ApiKey>, Error> {
+        sqlx::query("SELECT * from api_keys where account_id = $1 ORDER BY id DESC")
+            .bind(account_id.0)
+            .map(api_key_from_row)
+            .fetch_all(&self.connection)
+            .await
+            .map_err(database_error)
+    }
+    async fn get_api_key(&self, key_hash: String) -> Result<ApiKey, Error> {
+        sqlx::query("SELECT * from api_keys where key_hash = $1")
+            .bind(key_hash)
+            .map(api_key_from_row)
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(database_error)?
+            .ok_or(Error::NotFound)
+    }
+    async fn touch_api_key(&self, id: ApiKeyId) -> Result<bool, Error> {
+        let updated = sqlx::query("UPDATE api_keys SET last_used_on = NOW() WHERE id = $1")
+            .bind(id.0)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(updated.rows_affected() == 1)
+    }
+    async fn revoke_api_key(&self, account_id: AccountId, id: ApiKeyId) -> Result<bool, Error> {
+        let revoked = sqlx::query(
+            "UPDATE api_keys SET revoked_on = NOW() WHERE id = $1 AND account_id = $2 AND revoked_on IS NULL",
+        )
+            .bind(id.0)
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(revoked.rows_affected() == 1)
+    }
+    async fn add_oidc_login(&self, login: OidcLogin) -> Result<bool, Error> {
+        // Abandoned logins are cleaned up whenever a new one starts
+        sqlx::query("DELETE FROM oidc_logins WHERE expires_on < $1")
+            .bind(Utc::now().naive_utc())
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        let added = sqlx::query(
+            "INSERT INTO oidc_logins (state_hash, code_verifier, nonce, expires_on) VALUES ($1, $2, $3, $4)",
+        )
+            .bind(login.state_hash)
+            .bind(login.code_verifier)
+            .bind(login.nonce)
+            .bind(login.expires_on)
+            .execute(&self.connection)
+            .await
+            .map_err(database_error)?;
+        Ok(added.rows_affected() == 1)
+    }
+    async fn take_oidc_login(&self, state_hash: String) -> Result<OidcLogin, Error> {
+        sqlx::query("DELETE FROM oidc_logins WHERE state_hash = $1 RETURNING *")
+            .bind(state_hash)
+            .map(|row: PgRow| OidcLogin {
+                state_hash: row.get("state_hash"),
+                code_verifier: row.get("code_verifier"),
+                nonce: row.get("nonce"),
+                expires_on: row.get("expires_on"),
+            })
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(database_error)?
+            .ok_or(Error::NotFound)
+    }
 }
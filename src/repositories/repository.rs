@@ -1,8 +1,21 @@
 use std::sync::Arc;
+use chrono::NaiveDateTime;
 use crate::errors::Error;
-use crate::types::account::{Account, AccountId};
+use crate::types::account::{Account, AccountId, ProfileUpdate, Role};
 use crate::types::answer::{Answer, NewAnswer};
-use crate::types::question::{NewQuestion, Question};
+use crate::types::api_key::{ApiKey, ApiKeyId, NewApiKey};
+use crate::types::comment::{Comment, CommentTarget, NewComment};
+use crate::types::login_attempt::LoginFailures;
+use crate::types::moderation::{Actor, ModerationEntry};
+use crate::types::oidc::OidcLogin;
+use crate::types::pagination::{Page, Pagination};
+use crate::types::prompt::KnowledgeSource;
+use crate::types::question::{NewQuestion, Question, QuestionId, QuestionQuery, QuestionStatus};
+use crate::types::search::SearchResult;
+use crate::types::tag::TagCount;
+use crate::types::token::{AccountToken, AccountTokenPurpose, NewAccountToken, NewRefreshToken, RefreshToken};
+use crate::types::totp::Totp;
+use crate::types::vote::VoteDirection;
 use async_trait::async_trait;
 
 
@@ -12,9 +25,8 @@ pub type Repository = Arc<dyn RepositoryPort + Send + Sync>;
 pub trait RepositoryPort {
     async fn get_questions(
         &self,
-        limit: Option<i32>,
-        offset: i32,
-    ) -> Result<Vec<Question>, Error>;
+        query: QuestionQuery,
+    ) -> Result<Page<Question>, Error>;
     async fn get_question(
         &self,
         question_id: i32,
@@ -33,14 +45,138 @@ pub trait RepositoryPort {
         &self,
         question: Question,
         id: i32,
+        actor: Actor,
+    ) -> Result<Question, Error>;
+    async fn delete_question(&self, id: i32, actor: Actor) -> Result<bool, Error>;
+    async fn vote_question(
+        &self,
+        id: i32,
+        account_id: AccountId,
+        direction: VoteDirection,
+    ) -> Result<Question, Error>;
+    async fn accept_answer(
+        &self,
+        question_id: i32,
+        answer_id: i32,
+        account_id: AccountId,
+    ) -> Result<Question, Error>;
+    async fn update_question_status(
+        &self,
+        id: i32,
+        status: QuestionStatus,
         account_id: AccountId,
     ) -> Result<Question, Error>;
-    async fn delete_question(&self, id: i32, account_id: AccountId) -> Result<bool, Error>;
     async fn add_answer(
         &self,
         new_answer: NewAnswer,
         account_id: AccountId,
     ) -> Result<Answer, Error>;
+    async fn get_answers(
+        &self,
+        question_id: i32,
+    ) -> Result<Vec<Answer>, Error>;
+    async fn get_answer(
+        &self,
+        answer_id: i32,
+    ) -> Result<Answer, Error>;
+    async fn is_answer_owner(
+        &self,
+        answer_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error>;
+    async fn update_answer(
+        &self,
+        answer: Answer,
+        id: i32,
+        actor: Actor,
+    ) -> Result<Answer, Error>;
+    async fn delete_answer(&self, id: i32, actor: Actor) -> Result<bool, Error>;
+    async fn vote_answer(
+        &self,
+        id: i32,
+        account_id: AccountId,
+        direction: VoteDirection,
+    ) -> Result<Answer, Error>;
+    async fn add_comment(
+        &self,
+        target: CommentTarget,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error>;
+    async fn get_comments(&self, target: CommentTarget) -> Result<Vec<Comment>, Error>;
+    async fn is_comment_owner(
+        &self,
+        comment_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error>;
+    async fn delete_comment(
+        &self,
+        target: CommentTarget,
+        id: i32,
+        account_id: AccountId,
+    ) -> Result<bool, Error>;
+    async fn get_tags(&self) -> Result<Vec<TagCount>, Error>;
+    async fn search(&self, query: String, limit: i64) -> Result<Vec<SearchResult>, Error>;
+    /// Like `search`, but matches whatever contains any of the words in `terms`
+    async fn search_any(&self, terms: &[String], limit: i64) -> Result<Vec<SearchResult>, Error>;
+    /// The answered and resolved questions among `question_ids`, in their order,
+    /// each with its accepted answer or else the best scored one people wrote
+    async fn get_knowledge_sources(&self, question_ids: &[QuestionId]) -> Result<Vec<KnowledgeSource>, Error>;
     async fn add_account(&self, account: Account) -> Result<bool, Error>;
     async fn get_account(&self, email: String) -> Result<Account, Error>;
+    async fn get_account_by_id(&self, account_id: AccountId) -> Result<Account, Error>;
+    async fn update_account_role(&self, account_id: AccountId, role: Role) -> Result<bool, Error>;
+    async fn update_account_password(&self, account_id: AccountId, password: String) -> Result<bool, Error>;
+    async fn verify_account_email(&self, account_id: AccountId) -> Result<bool, Error>;
+    /// Sets the profile fields of `profile`, the password fields are left to `update_account_password`
+    async fn update_account_profile(&self, account_id: AccountId, profile: ProfileUpdate) -> Result<Account, Error>;
+    /// Removes the account with everything it signs in with. Its questions,
+    /// answers, comments and votes stay.
+    async fn delete_account(&self, account_id: AccountId) -> Result<bool, Error>;
+    /// Newest answers of the account first
+    async fn get_account_answers(&self, account_id: AccountId, limit: i64) -> Result<Vec<Answer>, Error>;
+    async fn add_account_token(&self, token: NewAccountToken) -> Result<AccountToken, Error>;
+    /// Marks the token as used and returns its account. Fails with `NotFound` when
+    /// the token is unknown, used, expired or meant for something else. Other
+    /// unused tokens of the account for the same purpose are used up as well.
+    async fn use_account_token(&self, purpose: AccountTokenPurpose, token_hash: String) -> Result<AccountId, Error>;
+    async fn get_moderation_log(&self, pagination: Pagination) -> Result<Page<ModerationEntry>, Error>;
+    async fn get_totp(&self, account_id: AccountId) -> Result<Totp, Error>;
+    /// Starts over with a new secret, returns `false` when two-factor authentication is already enabled
+    async fn set_totp_secret(&self, account_id: AccountId, secret: String) -> Result<bool, Error>;
+    /// Enables the secret with the step of its first code and replaces the recovery codes
+    async fn enable_totp(&self, account_id: AccountId, step: i64, recovery_code_hashes: Vec<String>) -> Result<bool, Error>;
+    async fn disable_totp(&self, account_id: AccountId) -> Result<bool, Error>;
+    /// Returns whether `step` is newer than the last one used, so each code works once
+    async fn use_totp_step(&self, account_id: AccountId, step: i64) -> Result<bool, Error>;
+    async fn use_recovery_code(&self, account_id: AccountId, code_hash: String) -> Result<bool, Error>;
+    async fn get_login_failures(&self, key: String) -> Result<Option<LoginFailures>, Error>;
+    /// Counts a failed login, starting over when the last one was before `window_start`
+    async fn add_login_failure(&self, key: String, window_start: NaiveDateTime) -> Result<LoginFailures, Error>;
+    async fn lock_login(&self, key: String, until: NaiveDateTime) -> Result<bool, Error>;
+    async fn clear_login_failures(&self, key: String) -> Result<bool, Error>;
+    async fn add_refresh_token(&self, token: NewRefreshToken) -> Result<RefreshToken, Error>;
+    async fn get_refresh_token(&self, token_hash: String) -> Result<RefreshToken, Error>;
+    /// Swaps the token of a session for a new one, returning whether `token_hash`
+    /// was still the current active token, so each one can be used only once
+    async fn rotate_refresh_token(
+        &self,
+        id: i32,
+        token_hash: String,
+        new_token_hash: String,
+        expires_on: NaiveDateTime,
+    ) -> Result<bool, Error>;
+    async fn revoke_refresh_token(&self, id: i32) -> Result<bool, Error>;
+    async fn revoke_account_tokens(&self, account_id: AccountId) -> Result<bool, Error>;
+    async fn is_session_active(&self, session_id: i32) -> Result<bool, Error>;
+    async fn add_api_key(&self, api_key: NewApiKey) -> Result<ApiKey, Error>;
+    /// Keys of the account including revoked ones, newest first
+    async fn get_api_keys(&self, account_id: AccountId) -> Result<Vec<ApiKey>, Error>;
+    async fn get_api_key(&self, key_hash: String) -> Result<ApiKey, Error>;
+    async fn touch_api_key(&self, id: ApiKeyId) -> Result<bool, Error>;
+    /// Only revokes keys of `account_id`, returning whether there was an active one
+    async fn revoke_api_key(&self, account_id: AccountId, id: ApiKeyId) -> Result<bool, Error>;
+    async fn add_oidc_login(&self, login: OidcLogin) -> Result<bool, Error>;
+    /// Removes the login, so each redirect from the provider is handled once
+    async fn take_oidc_login(&self, state_hash: String) -> Result<OidcLogin, Error>;
 }
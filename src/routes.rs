@@ -1,3 +1,12 @@
+pub mod account;
 pub mod answer;
+pub mod api_key;
 pub mod authentication;
+pub mod capabilities;
+pub mod comment;
+pub mod moderation;
+pub mod oidc;
 pub mod question;
+pub mod search;
+pub mod tag;
+pub mod two_factor;
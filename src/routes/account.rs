@@ -0,0 +1,139 @@
+use tracing::{event, Level};
+use warp::http::StatusCode;
+
+use crate::errors::Error;
+use crate::repositories::repository::Repository;
+use crate::routes::authentication::{hash_password, verify_password};
+use crate::types::account::{AccountId, OwnProfile, Profile, ProfileUpdate, PublicProfile, RoleUpdate, Session};
+use crate::types::login_attempt::LoginKey;
+use crate::types::pagination::{PageSize, Pagination};
+use crate::types::question::{QuestionQuery, QuestionSort};
+
+
+/// Takes effect the next time the account logs in, since the role travels in the token
+pub async fn update_role(
+    id: i32,
+    session: Session,
+    store: Repository,
+    update: RoleUpdate,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, admin = session.account_id.0, account = id, role = update.role.as_str());
+    match store.update_account_role(AccountId(id), update.role).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Account {} is now {}", id, update.role.as_str()),
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+/// Logs the account out everywhere, e.g. when someone leaves the company
+pub async fn revoke_sessions(
+    id: i32,
+    session: Session,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, admin = session.account_id.0, account = id, "revoking all sessions");
+    match store.revoke_account_tokens(AccountId(id)).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Sessions of account {} revoked", id),
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+/// Lifts a lockout after failed logins before it runs out
+pub async fn unlock(
+    id: i32,
+    session: Session,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account = store.get_account_by_id(AccountId(id)).await?;
+    event!(Level::INFO, admin = session.account_id.0, account = id, "unlocking login");
+    match store.clear_login_failures(LoginKey::Email(account.email).to_string()).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Account {} unlocked", id),
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+/// Public profile with the newest questions and answers of the account
+pub async fn get_profile(id: i32, page_size: PageSize, store: Repository) -> Result<impl warp::Reply, warp::Rejection> {
+    let account = store.get_account_by_id(AccountId(id)).await?;
+    let questions = store
+        .get_questions(QuestionQuery {
+            pagination: Pagination {
+                limit: page_size.default,
+                cursor: None,
+            },
+            sort: QuestionSort::Newest,
+            status: None,
+            tags: None,
+            author: Some(AccountId(id)),
+            created_after: None,
+            created_before: None,
+            has_answers: None,
+        })
+        .await?;
+    let answers = store.get_account_answers(AccountId(id), page_size.default).await?;
+
+    Ok(warp::reply::json(&PublicProfile {
+        profile: Profile::from(&account),
+        questions,
+        answers,
+    }))
+}
+
+pub async fn get_me(session: Session, store: Repository) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_account_by_id(session.account_id).await {
+        Ok(account) => Ok(warp::reply::json(&OwnProfile::from(&account))),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Replaces the profile. A new password takes the current one and logs the
+/// account out everywhere, like a reset does.
+pub async fn update_me(
+    session: Session,
+    store: Repository,
+    update: ProfileUpdate,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let update = update.normalized()?;
+    let account = store.get_account_by_id(session.account_id.clone()).await?;
+
+    let new_password = match (&update.current_password, &update.new_password) {
+        (Some(current_password), Some(new_password)) => {
+            let verified = verify_password(&account.password, current_password.as_bytes())
+                .map_err(Error::PasswordHashLibraryError)?;
+            if !verified {
+                return Err(warp::reject::custom(Error::WrongPassword));
+            }
+            Some(hash_password(new_password.as_bytes()).map_err(Error::PasswordHashLibraryError)?)
+        }
+        (None, Some(_)) => return Err(warp::reject::custom(Error::MissingParameters)),
+        _ => None,
+    };
+
+    let account = store.update_account_profile(session.account_id.clone(), update).await?;
+    if let Some(password) = new_password {
+        store.update_account_password(session.account_id.clone(), password).await?;
+        store.revoke_account_tokens(session.account_id).await?;
+    }
+    Ok(warp::reply::json(&OwnProfile::from(&account)))
+}
+
+/// Deletes the account of the session. What it posted stays, without a profile behind it.
+pub async fn delete_me(session: Session, store: Repository) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, account = session.account_id.0, "deleting account");
+    match store.delete_account(session.account_id).await {
+        Ok(true) => Ok(warp::reply::with_status("Account deleted", StatusCode::OK)),
+        Ok(false) => Err(warp::reject::custom(Error::NotFound)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
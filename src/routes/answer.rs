@@ -1,10 +1,10 @@
-
-
 use warp::http::StatusCode;
 use crate::repositories::repository::Repository;
 
 use crate::types::account::Session;
-use crate::types::answer::NewAnswer;
+use crate::types::answer::{Answer, NewAnswer};
+use crate::types::moderation::Actor;
+use crate::types::vote::NewVote;
 
 
 pub async fn add_answer(
@@ -19,3 +19,70 @@ pub async fn add_answer(
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
+
+
+pub async fn get_answers(
+    question_id: i32,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_answers(question_id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+pub async fn get_answer(
+    id: i32,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_answer(id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+pub async fn update_answer(
+    id: i32,
+    session: Session,
+    store: Repository,
+    answer: Answer,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let is_owner = store.is_answer_owner(id, &session.account_id).await?;
+    let actor = Actor::resolve(&session, is_owner)?;
+    match store.update_answer(answer, id, actor).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+pub async fn delete_answer(
+    id: i32,
+    session: Session,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let is_owner = store.is_answer_owner(id, &session.account_id).await?;
+    let actor = Actor::resolve(&session, is_owner)?;
+    match store.delete_answer(id, actor).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Answer {} deleted", id),
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+pub async fn vote_answer(
+    id: i32,
+    session: Session,
+    store: Repository,
+    vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.vote_answer(id, session.account_id, vote.direction).await {
+        Ok(answer) => Ok(warp::reply::json(&answer)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
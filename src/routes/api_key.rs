@@ -0,0 +1,78 @@
+use chrono::Utc;
+use tracing::{event, Level};
+use warp::http::StatusCode;
+
+use crate::errors::Error;
+use crate::repositories::repository::Repository;
+use crate::types::account::Session;
+use crate::types::api_key::{
+    generate_api_key, ApiKeyId, ApiKeyRequest, CreatedApiKey, NewApiKey, API_KEY_HINT_LENGTH, MAX_API_KEY_DAYS,
+};
+use crate::types::token::hash_token;
+
+/// Creates a key acting as the logged in account. The key is only part of
+/// this response, afterwards it can't be looked up again.
+pub async fn add_api_key(
+    session: Session,
+    store: Repository,
+    request: ApiKeyRequest,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let name = request.name.trim().to_string();
+    if name.is_empty() || name.len() > 255 {
+        return Err(warp::reject::custom(Error::InvalidParameter("name".to_string())));
+    }
+    if request.scopes.is_empty() {
+        return Err(warp::reject::custom(Error::InvalidParameter("scopes".to_string())));
+    }
+    let expires_on = match request.expires_in_days {
+        Some(days) if (1..=MAX_API_KEY_DAYS).contains(&days) => {
+            let expires_on = chrono::Duration::try_days(days).and_then(|lifetime| Utc::now().checked_add_signed(lifetime));
+            match expires_on {
+                Some(expires_on) => Some(expires_on.naive_utc()),
+                None => return Err(warp::reject::custom(Error::InvalidParameter("expires_in_days".to_string()))),
+            }
+        }
+        Some(_) => return Err(warp::reject::custom(Error::InvalidParameter("expires_in_days".to_string()))),
+        None => None,
+    };
+
+    let mut scopes = Vec::new();
+    for scope in request.scopes {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    let key = generate_api_key();
+    let api_key = store
+        .add_api_key(NewApiKey {
+            account_id: session.account_id,
+            name,
+            hint: key[..API_KEY_HINT_LENGTH].to_string(),
+            key_hash: hash_token(&key),
+            scopes,
+            expires_on,
+        })
+        .await?;
+    event!(Level::INFO, account = api_key.account_id.0, api_key = api_key.id.0, "API key created");
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&CreatedApiKey { key, api_key }),
+        StatusCode::CREATED,
+    ))
+}
+
+pub async fn get_api_keys(session: Session, store: Repository) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_api_keys(session.account_id).await {
+        Ok(api_keys) => Ok(warp::reply::json(&api_keys)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Takes effect immediately, keys are looked up on every request
+pub async fn revoke_api_key(id: i32, session: Session, store: Repository) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.revoke_api_key(session.account_id, ApiKeyId(id)).await {
+        Ok(true) => Ok(warp::reply::with_status(format!("API key {} revoked", id), StatusCode::OK)),
+        Ok(false) => Err(warp::reject::custom(Error::NotFound)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
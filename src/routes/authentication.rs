@@ -1,61 +1,325 @@
-use std::{env, future};
+use std::future;
+use std::net::IpAddr;
+use std::sync::{Arc, OnceLock};
 
 
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
 use argon2::{password_hash, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use chrono::prelude::*;
-
+use pasetors::claims::{Claims, ClaimsValidationRules};
+use pasetors::footer::Footer;
+use pasetors::token::UntrustedToken;
+use pasetors::version4::V4;
+use pasetors::Public;
+use rand::Rng;
+use tracing::{event, Level};
 use warp::{http::StatusCode, Filter};
 
 use crate::errors::Error;
 use crate::repositories::repository::Repository;
-use crate::types::account::{Account, AccountId, Session};
+use crate::routes::two_factor::check_second_factor;
+use crate::services::mailer::AccountMailer;
+use crate::types::account::{
+    is_valid_email, Account, AccountId, AdminEmails, EmailRequest, ProfileUpdate, Role, Session,
+};
+use crate::types::api_key::{ApiKeyGrant, Scope, API_KEY_PREFIX};
+use crate::types::key_ring::{read_footer, KeyFooter, KeyRing};
+use crate::types::login_attempt::{
+    failure_delay, forwarded_client, LockoutPolicy, LoginKey, EMAIL_LOCKOUT, IP_LOCKOUT,
+};
+use crate::types::token::{
+    hash_token, AccountTokenPurpose, EmailVerification, LoginChallenge, LoginResponse, NewAccountToken,
+    NewRefreshToken, PasswordReset, RefreshRequest, SecondFactorLogin, TokenPair,
+};
+
+/// Access tokens are checked against their session on every request, so they can stay short.
+/// A refresh extends the session by `REFRESH_TOKEN_DAYS` again.
+const ACCESS_TOKEN_MINUTES: i64 = 15;
+const REFRESH_TOKEN_DAYS: i64 = 30;
+/// Lifetime of the tokens mailed for verifying an email address and for resetting a password
+const VERIFY_EMAIL_HOURS: i64 = 24;
+const RESET_PASSWORD_HOURS: i64 = 1;
+/// Time between the password and the second factor of a login
+const LOGIN_CHALLENGE_MINUTES: i64 = 5;
 
 
-pub async fn register(store: Repository, account: Account) -> Result<impl warp::Reply, warp::Rejection> {
+pub async fn register(
+    mailer: AccountMailer,
+    store: Repository,
+    account: Account,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !is_valid_email(&account.email) {
+        return Err(warp::reject::custom(Error::InvalidParameter("email".to_string())));
+    }
+
     let hashed_password = match hash_password(account.password.as_bytes()) {
         Ok(hash) => hash,
         Err(e) => return Err(warp::reject::custom(Error::PasswordHashLibraryError(e))),
     };
 
+    let profile = ProfileUpdate {
+        display_name: account.display_name,
+        avatar_url: account.avatar_url,
+        bio: account.bio,
+        ..Default::default()
+    }
+    .normalized()?;
+
+    // Roles are only handed out by admins, see `routes::account::update_role`,
+    // and `ADMIN_EMAILS` on the first verified login
     let account = Account {
         id: account.id,
         email: account.email,
         password: hashed_password,
+        role: Role::User,
+        email_verified: false,
+        display_name: profile.display_name,
+        avatar_url: profile.avatar_url,
+        bio: profile.bio,
+        created_on: None,
     };
 
-    match store.add_account(account).await {
-        Ok(_) => Ok(warp::reply::with_status("Account added", StatusCode::OK)),
-        Err(e) => Err(warp::reject::custom(e)),
+    let email = account.email.clone();
+    if let Err(e) = store.add_account(account).await {
+        return Err(warp::reject::custom(e));
+    }
+    // The account stays, a failed mail can be sent again through `/email/verification`
+    let account = store.get_account(email).await?;
+    if let Err(e) = send_verification(&mailer, &store, &account).await {
+        event!(Level::ERROR, "{}", e);
     }
+
+    Ok(warp::reply::with_status("Account added", StatusCode::OK))
 }
 
+/// Mails a new verification link to accounts that aren't verified yet. Answers
+/// the same for unknown emails, so it can't be used to look up accounts.
+pub async fn resend_verification(
+    mailer: AccountMailer,
+    store: Repository,
+    request: EmailRequest,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_account(request.email).await {
+        Ok(account) if !account.email_verified => {
+            if let Err(e) = send_verification(&mailer, &store, &account).await {
+                event!(Level::ERROR, "{}", e);
+            }
+        }
+        Ok(_) => (),
+        Err(e) => event!(Level::INFO, "No verification mail sent: {}", e),
+    }
+
+    Ok(warp::reply::with_status(
+        "If the account needs verification, a link was sent",
+        StatusCode::OK,
+    ))
+}
+
+pub async fn verify_email(store: Repository, request: EmailVerification) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = use_account_token(&store, AccountTokenPurpose::VerifyEmail, &request.token).await?;
+    match store.verify_account_email(account_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Email verified", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
 
-pub async fn login(store: Repository, login: Account) -> Result<impl warp::Reply, warp::Rejection> {
-    match store.get_account(login.email).await {
-        Ok(account) => match verify_password(&account.password, login.password.as_bytes()) {
-            Ok(verified) => {
-                if verified {
-                    Ok(warp::reply::json(&issue_token(
-                        account.id.expect("id not found"),
-                    )))
-                } else {
-                    Err(warp::reject::custom(Error::WrongPassword))
-                }
+/// Mails a single-use reset link. Answers the same for unknown emails, so it
+/// can't be used to look up accounts.
+pub async fn forgot_password(
+    mailer: AccountMailer,
+    store: Repository,
+    request: EmailRequest,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_account(request.email).await {
+        Ok(account) => {
+            let sent = match issue_account_token(&store, &account, AccountTokenPurpose::ResetPassword).await {
+                Ok(token) => mailer.send_password_reset(&account.email, &token, RESET_PASSWORD_HOURS).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = sent {
+                event!(Level::ERROR, "{}", e);
             }
-            Err(e) => Err(warp::reject::custom(Error::PasswordHashLibraryError(e))),
-        },
+        }
+        Err(e) => event!(Level::INFO, "No reset mail sent: {}", e),
+    }
+
+    Ok(warp::reply::with_status(
+        "If the account exists, a reset link was sent",
+        StatusCode::OK,
+    ))
+}
+
+/// Sets a new password and logs the account out everywhere. Receiving the
+/// mail also proves the address, so it counts as verified afterwards.
+pub async fn reset_password(store: Repository, request: PasswordReset) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = use_account_token(&store, AccountTokenPurpose::ResetPassword, &request.token).await?;
+    let hashed_password = match hash_password(request.password.as_bytes()) {
+        Ok(hash) => hash,
+        Err(e) => return Err(warp::reject::custom(Error::PasswordHashLibraryError(e))),
+    };
+
+    store.update_account_password(account_id.clone(), hashed_password).await?;
+    store.verify_account_email(account_id.clone()).await?;
+    match store.revoke_account_tokens(account_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Password changed", StatusCode::OK)),
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
-pub fn verify_token(token: String) -> Result<Session, Error> {
-    let key = env::var("PASETO_KEY").unwrap();
+
+/// Answers the same for unknown emails and wrong passwords, both count as a
+/// failed login for the email and the client address
+pub async fn login(
+    keys: Arc<KeyRing>,
+    store: Repository,
+    admins: Arc<AdminEmails>,
+    ip: Option<IpAddr>,
+    login: Account,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let login_keys = login_keys(&login.email, ip);
+    check_login_locks(&store, &login_keys).await?;
+
+    let account = match store.get_account(login.email).await {
+        Ok(account) => Some(account),
+        Err(Error::NotFound) => None,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    // Unknown emails take as long as wrong passwords
+    let hash = account.as_ref().map(|a| a.password.as_str()).unwrap_or(dummy_hash());
+    let verified = verify_password(hash, login.password.as_bytes()).map_err(Error::PasswordHashLibraryError)?;
+    let account = match account {
+        Some(account) if verified => account,
+        _ => {
+            record_login_failure(&store, &login_keys).await?;
+            return Err(warp::reject::custom(Error::WrongPassword));
+        }
+    };
+    store.clear_login_failures(login_keys[0].to_string()).await?;
+
+    Ok(warp::reply::json(&complete_login(&keys, &store, &admins, account, false).await?))
+}
+
+/// Hands out tokens once the account proved who it is, or a challenge when it
+/// has two-factor authentication. `mfa` is whether that proof already was one.
+pub async fn complete_login(
+    keys: &KeyRing,
+    store: &Repository,
+    admins: &AdminEmails,
+    account: Account,
+    mfa: bool,
+) -> Result<LoginResponse, Error> {
+    if !account.email_verified {
+        return Err(Error::EmailNotVerified);
+    }
+    // Only after verification, registering someone else's address must not make an admin
+    let account = if account.role != Role::Admin && admins.contains(&account.email) {
+        store.update_account_role(account.id.clone().expect("id not found"), Role::Admin).await?;
+        event!(Level::WARN, account = account.id.as_ref().map(|id| id.0), "promoted to admin by ADMIN_EMAILS");
+        Account {
+            role: Role::Admin,
+            ..account
+        }
+    } else {
+        account
+    };
+    if !mfa && has_second_factor(store, account.id.clone().expect("id not found")).await? {
+        let challenge_token = issue_account_token(store, &account, AccountTokenPurpose::LoginChallenge).await?;
+        return Ok(LoginResponse::Challenge(LoginChallenge {
+            challenge_token,
+            expires_in: LOGIN_CHALLENGE_MINUTES * 60,
+        }));
+    }
+    let tokens = issue_tokens(keys, store, account.id.expect("id not found"), account.role, mfa).await?;
+    Ok(LoginResponse::Tokens(tokens))
+}
+
+/// Second step of a login with two-factor authentication. The challenge is
+/// used up by any attempt, a wrong code means logging in again.
+pub async fn login_second_factor(
+    keys: Arc<KeyRing>,
+    store: Repository,
+    ip: Option<IpAddr>,
+    login: SecondFactorLogin,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = use_account_token(&store, AccountTokenPurpose::LoginChallenge, &login.challenge_token).await?;
+    let account = store.get_account_by_id(account_id.clone()).await?;
+    let login_keys = login_keys(&account.email, ip);
+    check_login_locks(&store, &login_keys).await?;
+    if !check_second_factor(&store, &account_id, &login.factor).await? {
+        record_login_failure(&store, &login_keys).await?;
+        return Err(warp::reject::custom(Error::WrongSecondFactor));
+    }
+
+    store.clear_login_failures(login_keys[0].to_string()).await?;
+    let tokens = issue_tokens(&keys, &store, account.id.expect("id not found"), account.role, true).await?;
+    Ok(warp::reply::json(&tokens))
+}
+
+/// Trades a refresh token for a new pair within the same session. The old
+/// refresh token stops working, so each one can be used exactly once.
+pub async fn refresh(
+    keys: Arc<KeyRing>,
+    store: Repository,
+    request: RefreshRequest,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let token = match store.get_refresh_token(hash_token(&request.refresh_token)).await {
+        Ok(token) => token,
+        Err(Error::NotFound) => return Err(warp::reject::custom(Error::InvalidToken)),
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    if !token.is_active(Utc::now().naive_utc()) {
+        return Err(warp::reject::custom(Error::InvalidToken));
+    }
+
+    let refresh_token = new_token();
+    if !store
+        .rotate_refresh_token(token.id, token.token_hash, hash_token(&refresh_token), refresh_expiry())
+        .await?
+    {
+        return Err(warp::reject::custom(Error::InvalidToken));
+    }
+
+    // Picks up role changes made since the last login
+    let account = store.get_account_by_id(token.account_id).await?;
+    Ok(warp::reply::json(&TokenPair {
+        access_token: issue_token(&keys, account.id.expect("id not found"), account.role, token.id, token.mfa),
+        refresh_token,
+        expires_in: ACCESS_TOKEN_MINUTES * 60,
+    }))
+}
+
+
+/// Ends the session of the access token, together with its refresh token
+pub async fn logout(session: Session, store: Repository) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.revoke_refresh_token(session.session_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Logged out", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Verification keys for `v4.public` tokens, so other services can check
+/// sessions without holding any secret
+pub async fn get_public_keys(keys: Arc<KeyRing>) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&keys.published_keys()))
+}
+
+/// Decrypts the token with the key named in its footer, so tokens from
+/// previous keys stay valid while those keys are in the ring. Public tokens
+/// are checked against the public key named in their footer instead.
+pub fn verify_token(keys: &KeyRing, token: String) -> Result<Session, Error> {
+    let (footer, key_footer) = read_footer(&token).ok_or(Error::CannotDecryptToken)?;
+    if token.starts_with("v4.public.") {
+        return verify_public_token(keys, &token, &key_footer.kid);
+    }
+
+    let key = keys.get(&key_footer.kid).ok_or(Error::CannotDecryptToken)?;
     let token = paseto::tokens::validate_local_token(
         &token,
-        None,
-        key.as_bytes(),
+        Some(&footer),
+        key,
         &paseto::tokens::TimeBackend::Chrono,
     )
     .map_err(|_| Error::CannotDecryptToken)?;
@@ -63,7 +327,16 @@ pub fn verify_token(token: String) -> Result<Session, Error> {
     serde_json::from_value::<Session>(token).map_err(|_| Error::CannotDecryptToken)
 }
 
-fn hash_password(password: &[u8]) -> Result<String, password_hash::Error> {
+fn verify_public_token(keys: &KeyRing, token: &str, kid: &str) -> Result<Session, Error> {
+    let key = keys.public_key(kid).ok_or(Error::CannotDecryptToken)?;
+    let token = UntrustedToken::<Public, V4>::try_from(token).map_err(|_| Error::CannotDecryptToken)?;
+    let token = pasetors::public::verify(key, &token, &ClaimsValidationRules::new(), None, None)
+        .map_err(|_| Error::CannotDecryptToken)?;
+
+    serde_json::from_str::<Session>(token.payload()).map_err(|_| Error::CannotDecryptToken)
+}
+
+pub fn hash_password(password: &[u8]) -> Result<String, password_hash::Error> {
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
     match argon2.hash_password(password, &salt) {
@@ -72,35 +345,288 @@ fn hash_password(password: &[u8]) -> Result<String, password_hash::Error> {
     }
 }
 
-fn verify_password(hash: &str, password: &[u8]) -> Result<bool, password_hash::Error> {
+pub fn verify_password(hash: &str, password: &[u8]) -> Result<bool, password_hash::Error> {
     match Argon2::default().verify_password(password, &PasswordHash::new(hash)?) {
         Ok(_) => Ok(true),
+        Err(password_hash::Error::Password) => Ok(false),
         Err(err) => Err(err),
     }
 }
 
-fn issue_token(account_id: AccountId) -> String {
-    let key = env::var("PASETO_KEY").unwrap();
+/// Hash of no account's password, checked against for unknown emails
+fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| hash_password(&rand::thread_rng().gen::<[u8; 32]>()).expect("Failed to hash dummy password"))
+}
+
+/// The email comes first, it's the key a successful login clears
+fn login_keys(email: &str, ip: Option<IpAddr>) -> Vec<LoginKey> {
+    let mut keys = vec![LoginKey::Email(email.to_string())];
+    keys.extend(ip.map(LoginKey::Ip));
+    keys
+}
+
+fn lockout_policy(key: &LoginKey) -> LockoutPolicy {
+    match key {
+        LoginKey::Email(_) => EMAIL_LOCKOUT,
+        LoginKey::Ip(_) => IP_LOCKOUT,
+    }
+}
+
+async fn check_login_locks(store: &Repository, keys: &[LoginKey]) -> Result<(), Error> {
+    let now = Utc::now().naive_utc();
+    for key in keys {
+        let failures = store.get_login_failures(key.to_string()).await?;
+        if failures.and_then(|f| f.locked_until).map(|until| until > now).unwrap_or(false) {
+            return Err(Error::TooManyAttempts);
+        }
+    }
+    Ok(())
+}
+
+/// Counts the failure for every key, locks the keys over their limit and
+/// waits longer the more often the email failed
+async fn record_login_failure(store: &Repository, keys: &[LoginKey]) -> Result<(), Error> {
+    let now = Utc::now().naive_utc();
+    let mut delay = failure_delay(1);
+    for key in keys {
+        let policy = lockout_policy(key);
+        let window_start = now - chrono::Duration::minutes(policy.window_minutes);
+        let failures = store.add_login_failure(key.to_string(), window_start).await?;
+        if failures.failures >= policy.max_failures {
+            event!(Level::WARN, key = failures.key, failures = failures.failures, "locking login");
+            store
+                .lock_login(failures.key, now + chrono::Duration::minutes(policy.lockout_minutes))
+                .await?;
+        }
+        if let LoginKey::Email(_) = key {
+            delay = failure_delay(failures.failures);
+        }
+    }
+    tokio::time::sleep(delay).await;
+    Ok(())
+}
+
+/// 32 random bytes, for refresh tokens and mailed tokens
+pub fn new_token() -> String {
+    URL_SAFE_NO_PAD.encode(rand::thread_rng().gen::<[u8; 32]>())
+}
+
+fn refresh_expiry() -> NaiveDateTime {
+    (Utc::now() + chrono::Duration::days(REFRESH_TOKEN_DAYS)).naive_utc()
+}
+
+/// Stores the hash of a new single-use token and returns the token itself
+async fn issue_account_token(store: &Repository, account: &Account, purpose: AccountTokenPurpose) -> Result<String, Error> {
+    let valid_for = match purpose {
+        AccountTokenPurpose::VerifyEmail => chrono::Duration::hours(VERIFY_EMAIL_HOURS),
+        AccountTokenPurpose::ResetPassword => chrono::Duration::hours(RESET_PASSWORD_HOURS),
+        AccountTokenPurpose::LoginChallenge => chrono::Duration::minutes(LOGIN_CHALLENGE_MINUTES),
+    };
+    let token = new_token();
+    store
+        .add_account_token(NewAccountToken {
+            account_id: account.id.clone().expect("id not found"),
+            purpose,
+            token_hash: hash_token(&token),
+            expires_on: (Utc::now() + valid_for).naive_utc(),
+        })
+        .await?;
+    Ok(token)
+}
+
+async fn send_verification(mailer: &AccountMailer, store: &Repository, account: &Account) -> Result<(), Error> {
+    let token = issue_account_token(store, account, AccountTokenPurpose::VerifyEmail).await?;
+    mailer.send_verification(&account.email, &token, VERIFY_EMAIL_HOURS).await
+}
+
+async fn use_account_token(store: &Repository, purpose: AccountTokenPurpose, token: &str) -> Result<AccountId, Error> {
+    match store.use_account_token(purpose, hash_token(token)).await {
+        Err(Error::NotFound) => Err(Error::InvalidToken),
+        result => result,
+    }
+}
+
+async fn has_second_factor(store: &Repository, account_id: AccountId) -> Result<bool, Error> {
+    match store.get_totp(account_id).await {
+        Ok(totp) => Ok(totp.is_enabled()),
+        Err(Error::NotFound) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Starts a new session: a refresh token stored server-side and an access token bound to it
+async fn issue_tokens(
+    keys: &KeyRing,
+    store: &Repository,
+    account_id: AccountId,
+    role: Role,
+    mfa: bool,
+) -> Result<TokenPair, Error> {
+    let refresh_token = new_token();
+    let session = store
+        .add_refresh_token(NewRefreshToken {
+            account_id: account_id.clone(),
+            token_hash: hash_token(&refresh_token),
+            expires_on: refresh_expiry(),
+            mfa,
+        })
+        .await?;
+
+    Ok(TokenPair {
+        access_token: issue_token(keys, account_id, role, session.id, mfa),
+        refresh_token,
+        expires_in: ACCESS_TOKEN_MINUTES * 60,
+    })
+}
+
+fn issue_token(keys: &KeyRing, account_id: AccountId, role: Role, session_id: i32, mfa: bool) -> String {
+    if keys.signing_key().is_some() {
+        return issue_public_token(keys, account_id, role, session_id, mfa);
+    }
+
+    let (kid, key) = keys.current();
+    let footer = serde_json::to_string(&KeyFooter { kid: kid.to_string() }).expect("footer is plain json");
 
     let current_date_time = Utc::now();
-    let dt = current_date_time + chrono::Duration::days(1);
+    let dt = current_date_time + chrono::Duration::minutes(ACCESS_TOKEN_MINUTES);
 
     paseto::tokens::PasetoBuilder::new()
-        .set_encryption_key(&Vec::from(key.as_bytes()))
+        .set_encryption_key(key)
+        .set_footer(&footer)
         .set_expiration(&dt)
         .set_not_before(&Utc::now())
         .set_claim("account_id", serde_json::json!(account_id))
+        .set_claim("role", serde_json::json!(role))
+        .set_claim("session_id", serde_json::json!(session_id))
+        .set_claim("mfa", serde_json::json!(mfa))
         .build()
         .expect("Failed to construct paseto token w/ builder!")
 }
 
-pub fn auth() -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
-    warp::header::<String>("Authorization").and_then(|token: String| {
-        let token = match verify_token(token) {
-            Ok(t) => t,
-            Err(_) => return future::ready(Err(warp::reject::reject())),
-        };
+/// Same claims as the local tokens, signed instead of encrypted, so anyone
+/// with the published key can read and check them
+fn issue_public_token(keys: &KeyRing, account_id: AccountId, role: Role, session_id: i32, mfa: bool) -> String {
+    let (kid, key) = keys.signing_key().expect("no signing key");
+    let mut footer = Footer::new();
+    footer.key_id(kid);
+
+    let mut claims = Claims::new_expires_in(&std::time::Duration::from_secs(ACCESS_TOKEN_MINUTES as u64 * 60))
+        .expect("Failed to set token expiry");
+    claims.add_additional("account_id", serde_json::json!(account_id)).expect("account_id is not reserved");
+    claims.add_additional("role", serde_json::json!(role)).expect("role is not reserved");
+    claims.add_additional("session_id", serde_json::json!(session_id)).expect("session_id is not reserved");
+    claims.add_additional("mfa", serde_json::json!(mfa)).expect("mfa is not reserved");
+
+    pasetors::public::sign(key, &claims, Some(&footer), None).expect("Failed to sign paseto token")
+}
+
+/// Address of the client, from the last `X-Forwarded-For` entry when the
+/// service runs behind a trusted proxy, see [`forwarded_client`]
+pub fn client_ip(trust_proxy: bool) -> impl Filter<Extract = (Option<IpAddr>,), Error = warp::Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .map(move |remote: Option<std::net::SocketAddr>, forwarded_for: Option<String>| {
+            let forwarded = forwarded_for
+                .filter(|_| trust_proxy)
+                .and_then(|header| forwarded_client(&header));
+            forwarded.or(remote.map(|addr| addr.ip()))
+        })
+}
+
+/// Accepts valid access tokens whose session hasn't been revoked through
+/// `/logout` or by an admin, and active API keys that have `scope`
+pub fn auth(
+    keys: Arc<KeyRing>,
+    store: Repository,
+    scope: Scope,
+) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    warp::header::<String>("Authorization").and_then(move |token: String| {
+        let keys = keys.clone();
+        let store = store.clone();
+        async move {
+            let session = if token.starts_with(API_KEY_PREFIX) {
+                api_key_session(&store, &token).await?
+            } else {
+                token_session(&keys, &store, token).await?
+            };
+
+            if session.allows(scope) {
+                Ok(session)
+            } else {
+                Err(warp::reject::custom(Error::MissingScope(scope)))
+            }
+        }
+    })
+}
+
+/// Like [`auth`] without API keys, for managing the account itself
+pub fn user_auth(
+    keys: Arc<KeyRing>,
+    store: Repository,
+) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    warp::header::<String>("Authorization").and_then(move |token: String| {
+        let keys = keys.clone();
+        let store = store.clone();
+        async move { token_session(&keys, &store, token).await }
+    })
+}
+
+async fn token_session(keys: &KeyRing, store: &Repository, token: String) -> Result<Session, warp::Rejection> {
+    let session = match verify_token(keys, token) {
+        Ok(t) => t,
+        Err(_) => return Err(warp::reject::reject()),
+    };
+
+    match store.is_session_active(session.session_id).await {
+        Ok(true) => Ok(session),
+        Ok(false) => Err(warp::reject::custom(Error::InvalidToken)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Acts as the owner of the key with their current role. Keys never pass
+/// two-factor authentication, so they can't be used for staff actions.
+async fn api_key_session(store: &Repository, key: &str) -> Result<Session, warp::Rejection> {
+    let api_key = match store.get_api_key(hash_token(key)).await {
+        Ok(api_key) if api_key.is_active(Utc::now().naive_utc()) => api_key,
+        Ok(_) | Err(Error::NotFound) => return Err(warp::reject::custom(Error::InvalidToken)),
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let account = store.get_account_by_id(api_key.account_id.clone()).await?;
+    store.touch_api_key(api_key.id).await?;
+
+    Ok(Session {
+        exp: api_key
+            .expires_on
+            .map(|expires_on| expires_on.and_utc())
+            .unwrap_or(DateTime::<Utc>::MAX_UTC),
+        nbf: api_key.created_on.and_utc(),
+        account_id: api_key.account_id,
+        session_id: 0,
+        role: account.role,
+        mfa: false,
+        api_key: Some(ApiKeyGrant {
+            id: api_key.id,
+            scopes: api_key.scopes,
+        }),
+    })
+}
 
-        future::ready(Ok(token))
+/// Like [`user_auth`], but also rejects sessions whose role is below `role`. Staff
+/// only routes also need a login that passed two-factor authentication.
+pub fn require_role(
+    keys: Arc<KeyRing>,
+    store: Repository,
+    role: Role,
+) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    user_auth(keys, store).and_then(move |session: Session| {
+        if session.role < role {
+            future::ready(Err(warp::reject::custom(Error::Unauthorized)))
+        } else if role > Role::User && !session.mfa {
+            future::ready(Err(warp::reject::custom(Error::SecondFactorRequired)))
+        } else {
+            future::ready(Ok(session))
+        }
     })
 }
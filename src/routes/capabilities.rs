@@ -0,0 +1,15 @@
+use std::sync::Arc;
+
+use crate::services::ai_provider::SharedAiProvider;
+use crate::services::oidc::OidcClient;
+use crate::types::capabilities::Capabilities;
+
+pub async fn get_capabilities(
+    ai: Option<SharedAiProvider>,
+    oidc: Option<Arc<OidcClient>>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&Capabilities {
+        ai_answers: ai.is_some(),
+        single_sign_on: oidc.is_some(),
+    }))
+}
@@ -0,0 +1,51 @@
+use warp::http::StatusCode;
+
+use crate::errors::Error;
+use crate::repositories::repository::Repository;
+use crate::types::account::Session;
+use crate::types::comment::{CommentTarget, NewComment};
+
+
+pub async fn add_comment(
+    target: CommentTarget,
+    session: Session,
+    store: Repository,
+    new_comment: NewComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.add_comment(target, new_comment, session.account_id).await {
+        Ok(comment) => Ok(warp::reply::json(&comment)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+pub async fn get_comments(
+    target: CommentTarget,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_comments(target).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+pub async fn delete_comment(
+    target: CommentTarget,
+    id: i32,
+    session: Session,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    if store.is_comment_owner(id, &account_id).await? {
+        match store.delete_comment(target, id, account_id).await {
+            Ok(_) => Ok(warp::reply::with_status(
+                format!("Comment {} deleted", id),
+                StatusCode::OK,
+            )),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
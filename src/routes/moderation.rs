@@ -0,0 +1,19 @@
+use std::collections::HashMap;
+
+use crate::repositories::repository::Repository;
+use crate::types::account::Session;
+use crate::types::pagination::{extract_pagination, PageSize};
+
+
+pub async fn get_moderation_log(
+    params: HashMap<String, String>,
+    page_size: PageSize,
+    _session: Session,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pagination = extract_pagination(&params, page_size)?;
+    match store.get_moderation_log(pagination).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,138 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::Utc;
+use tracing::{event, Level};
+use warp::http::Uri;
+
+use crate::errors::Error;
+use crate::repositories::repository::Repository;
+use crate::routes::authentication::{complete_login, hash_password, new_token};
+use crate::services::oidc::OidcClient;
+use crate::types::account::{Account, AdminEmails, Role};
+use crate::types::key_ring::KeyRing;
+use crate::types::oidc::{pkce_challenge, OidcCallback, OidcLogin};
+use crate::types::token::hash_token;
+
+/// Time the user has to sign in at the identity provider
+const OIDC_LOGIN_MINUTES: i64 = 10;
+
+/// Starts single sign-on by sending the browser to the identity provider
+pub async fn start_login(
+    client: Option<Arc<OidcClient>>,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let client = client.ok_or(Error::NotFound)?;
+    let metadata = client.provider_metadata().await?;
+
+    let state = new_token();
+    let nonce = new_token();
+    let code_verifier = new_token();
+    let url = client.authorization_url(&metadata, &state, &nonce, &pkce_challenge(&code_verifier))?;
+    store
+        .add_oidc_login(OidcLogin {
+            state_hash: hash_token(&state),
+            code_verifier,
+            nonce,
+            expires_on: (Utc::now() + chrono::Duration::minutes(OIDC_LOGIN_MINUTES)).naive_utc(),
+        })
+        .await?;
+
+    let uri = Uri::from_str(url.as_str()).map_err(|e| Error::OidcError(e.to_string()))?;
+    Ok(warp::redirect::found(uri))
+}
+
+/// Where the identity provider sends the browser back to. Signs in the
+/// account with the email of the ID token, creating it on the first login.
+pub async fn callback(
+    client: Option<Arc<OidcClient>>,
+    keys: Arc<KeyRing>,
+    store: Repository,
+    admins: Arc<AdminEmails>,
+    callback: OidcCallback,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let client = client.ok_or(Error::NotFound)?;
+    if let Some(error) = callback.error {
+        let message = match callback.error_description {
+            Some(description) => format!("{}: {}", error, description),
+            None => error,
+        };
+        return Err(warp::reject::custom(Error::OidcError(message)));
+    }
+    let (code, state) = match (callback.code, callback.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(warp::reject::custom(Error::MissingParameters)),
+    };
+
+    let login = match store.take_oidc_login(hash_token(&state)).await {
+        Ok(login) if login.expires_on > Utc::now().naive_utc() => login,
+        Ok(_) | Err(Error::NotFound) => return Err(warp::reject::custom(Error::InvalidToken)),
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let metadata = client.provider_metadata().await?;
+    let tokens = client.exchange_code(&metadata, &code, &login.code_verifier).await?;
+    let claims = client.verify_id_token(&metadata, &tokens.id_token, &login.nonce).await?;
+
+    let email = claims
+        .email
+        .clone()
+        .ok_or_else(|| Error::OidcError("ID token without email, is the email scope allowed?".to_string()))?;
+    if claims.email_verified == Some(false) {
+        return Err(warp::reject::custom(Error::OidcError(format!("{} is not verified", email))));
+    }
+    // Providers that let users type in any address would otherwise hand out existing accounts
+    let email_verified = claims.email_verified == Some(true);
+
+    let account = match store.get_account(email.clone()).await {
+        Ok(_) if !email_verified => {
+            return Err(warp::reject::custom(Error::OidcError(format!(
+                "{} is not verified by the provider, cannot link it to an account",
+                email
+            ))));
+        }
+        Ok(account) if account.email_verified => account,
+        Ok(account) => {
+            // The provider vouches for the address, so the pending verification is done
+            store.verify_account_email(account.id.clone().expect("id not found")).await?;
+            Account {
+                email_verified: true,
+                ..account
+            }
+        }
+        Err(Error::NotFound) if email_verified || client.provision_unverified() => {
+            provision_account(&store, &email).await?
+        }
+        Err(Error::NotFound) => {
+            return Err(warp::reject::custom(Error::OidcError(format!(
+                "{} is not verified by the provider",
+                email
+            ))));
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    event!(Level::INFO, account = account.id.as_ref().map(|id| id.0), subject = claims.sub, "single sign-on");
+
+    Ok(warp::reply::json(
+        &complete_login(&keys, &store, &admins, account, claims.passed_mfa()).await?,
+    ))
+}
+
+/// Accounts created through single sign-on get a random password, a password
+/// login needs a reset first
+async fn provision_account(store: &Repository, email: &str) -> Result<Account, Error> {
+    let password = hash_password(new_token().as_bytes()).map_err(Error::PasswordHashLibraryError)?;
+    store
+        .add_account(Account {
+            id: None,
+            email: email.to_string(),
+            password,
+            role: Role::User,
+            email_verified: true,
+            display_name: None,
+            avatar_url: None,
+            bio: None,
+            created_on: None,
+        })
+        .await?;
+    store.get_account(email.to_string()).await
+}
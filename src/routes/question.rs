@@ -1,36 +1,71 @@
-use std::collections::HashMap;
-
-
+use std::future;
+use std::sync::Arc;
 
 use tracing::{event, Level};
+use warp::http::header::{HeaderValue, LINK};
 use warp::http::StatusCode;
+use warp::{Filter, Reply};
 
 use crate::errors::Error;
 use crate::repositories::repository::Repository;
-use crate::services::google_ai_service::get_ai_content;
+use crate::services::ai_provider::SharedAiProvider;
 use crate::types::account::Session;
-use crate::types::answer::NewAnswer;
-use crate::types::pagination::{extract_pagination, Pagination};
-use crate::types::question::{NewQuestion, Question, QuestionId};
+use crate::types::answer::{GeneratedAnswer, NewAnswer};
+use crate::types::moderation::Actor;
+use crate::types::pagination::PageSize;
+use crate::types::prompt::{retrieval_terms, KnowledgeSource, PromptTemplate, MAX_SOURCES};
+use crate::types::question::{
+    extract_question_query, NewQuestion, Question, QuestionId, QuestionQuery, QuestionStatus,
+    QuestionWithAnswers, StatusUpdate,
+};
+use crate::types::vote::NewVote;
+
+
+/// Extracts the `/questions` query parameters into a [`QuestionQuery`]
+pub fn question_query(
+    page_size: PageSize,
+) -> impl Filter<Extract = (QuestionQuery,), Error = warp::Rejection> + Clone {
+    warp::query::<Vec<(String, String)>>().and_then(move |query: Vec<(String, String)>| {
+        future::ready(extract_question_query(&query, page_size).map_err(warp::reject::custom))
+    })
+}
 
 
 pub async fn get_questions(
-    params: HashMap<String, String>,
+    query: QuestionQuery,
     store: Repository,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     event!(target: "practical_rust_book", Level::INFO, "querying questions");
-    let mut pagination = Pagination::default();
-
-    if !params.is_empty() {
+    if query.pagination.cursor.is_some() {
         event!(Level::INFO, pagination = true);
-        pagination = extract_pagination(params)?;
     }
+    let params = query.to_params();
 
-    match store
-        .get_questions(pagination.limit, pagination.offset)
-        .await
-    {
-        Ok(res) => Ok(warp::reply::json(&res)),
+    match store.get_questions(query).await {
+        Ok(page) => {
+            let link = page.link_header("/questions", &params);
+            let mut response = warp::reply::json(&page).into_response();
+            if let Some(value) = link.and_then(|link| HeaderValue::from_str(&link).ok()) {
+                response.headers_mut().insert(LINK, value);
+            }
+            Ok(response)
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+
+pub async fn get_question(
+    id: i32,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let question = match store.get_question(id).await {
+        Ok(question) => question,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+
+    match store.get_answers(id).await {
+        Ok(answers) => Ok(warp::reply::json(&QuestionWithAnswers { question, answers })),
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
@@ -42,20 +77,11 @@ pub async fn update_question(
     store: Repository,
     question: Question,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let account_id = session.account_id;
-    if store.is_question_owner(id, &account_id).await? {
-        let question = Question {
-            id: question.id,
-            title: question.title,
-            content: question.content,
-            tags: question.tags,
-        };
-        match store.update_question(question, id, account_id).await {
-            Ok(res) => Ok(warp::reply::json(&res)),
-            Err(e) => Err(warp::reject::custom(e)),
-        }
-    } else {
-        Err(warp::reject::custom(Error::Unauthorized))
+    let is_owner = store.is_question_owner(id, &session.account_id).await?;
+    let actor = Actor::resolve(&session, is_owner)?;
+    match store.update_question(question, id, actor).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
@@ -65,17 +91,14 @@ pub async fn delete_question(
     session: Session,
     store: Repository,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let account_id = session.account_id;
-    if store.is_question_owner(id, &account_id).await? {
-        match store.delete_question(id, account_id).await {
-            Ok(_) => Ok(warp::reply::with_status(
-                format!("Question {} deleted", id),
-                StatusCode::OK,
-            )),
-            Err(e) => Err(warp::reject::custom(e)),
-        }
-    } else {
-        Err(warp::reject::custom(Error::Unauthorized))
+    let is_owner = store.is_question_owner(id, &session.account_id).await?;
+    let actor = Actor::resolve(&session, is_owner)?;
+    match store.delete_question(id, actor).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Question {} deleted", id),
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
@@ -99,11 +122,111 @@ pub async fn add_question(
     }
 }
 
+pub async fn accept_answer(
+    id: i32,
+    answer_id: i32,
+    session: Session,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    if store.is_question_owner(id, &account_id).await? {
+        match store.accept_answer(id, answer_id, account_id).await {
+            Ok(question) => Ok(warp::reply::json(&question)),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
+
+
+pub async fn update_question_status(
+    id: i32,
+    session: Session,
+    store: Repository,
+    update: StatusUpdate,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    // `answered` and `resolved` follow from the answers, they can't be set directly
+    if !matches!(update.status, QuestionStatus::Open | QuestionStatus::Closed) {
+        return Err(warp::reject::custom(Error::InvalidParameter(format!(
+            "status={}",
+            update.status.as_str()
+        ))));
+    }
+
+    let account_id = session.account_id;
+    if store.is_question_owner(id, &account_id).await? {
+        match store.update_question_status(id, update.status, account_id).await {
+            Ok(question) => Ok(warp::reply::json(&question)),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(Error::Unauthorized))
+    }
+}
+
+
+pub async fn vote_question(
+    id: i32,
+    session: Session,
+    store: Repository,
+    vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.vote_question(id, session.account_id, vote.direction).await {
+        Ok(question) => Ok(warp::reply::json(&question)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Answers the question with the AI provider and stores the answer
+/// # Example usage
+/// ```rust
+/// use std::sync::Arc;
+/// use chrono::Utc;
+/// use warp::Reply;
+/// use rush::repositories::memory_repository::MemoryRepository;
+/// use rush::repositories::repository::Repository;
+/// use rush::routes::question::add_answer;
+/// use rush::services::ai_provider::{FakeAiProvider, SharedAiProvider};
+/// use rush::types::account::{AccountId, Role, Session};
+/// use rush::types::prompt::PromptTemplate;
+/// use rush::types::question::NewQuestion;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let store: Repository = Arc::new(MemoryRepository::new());
+/// let ai: SharedAiProvider = Arc::new(FakeAiProvider::new());
+/// let template = PromptTemplate {
+///     version: "test".to_string(),
+///     system: "Be brief.".to_string(),
+///     user: "{title}".to_string(),
+///     token_budget: 100,
+/// };
+/// let question = NewQuestion { title: "Borrowing".to_string(), content: "Why?".to_string(), tags: None };
+/// let question = store.add_question(question, AccountId(1)).await.unwrap();
+/// let session = Session { exp: Utc::now(), nbf: Utc::now(), account_id: AccountId(2), session_id: 1, role: Role::User, mfa: false, api_key: None };
+///
+/// let reply = add_answer(question.id.0, session, store.clone(), Some(ai), Arc::new(template)).await.unwrap();
+/// let body = warp::hyper::body::to_bytes(reply.into_response().into_body()).await.unwrap();
+/// let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+/// assert_eq!(body["content"], "Generated answer to: Borrowing");
+/// assert_eq!(body["prompt_version"], "test");
+/// assert_eq!(body["sources"], serde_json::json!([]));
+///
+/// let answers = store.get_answers(question.id.0).await.unwrap();
+/// assert_eq!(answers.len(), 1);
+/// assert_eq!(answers[0].content, "Generated answer to: Borrowing");
+/// assert_eq!(answers[0].account_id, Some(AccountId(2)));
+/// assert_eq!(answers[0].prompt_version.as_deref(), Some("test"));
+/// # });
+/// ```
 pub async fn add_answer(
     id: i32,
     session: Session,
     store: Repository,
+    ai: Option<SharedAiProvider>,
+    template: Arc<PromptTemplate>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
+    let ai = ai.ok_or(Error::AiDisabled)?;
     let account_id = session.account_id;
 
     let question = match store.get_question(id).await {
@@ -111,7 +234,12 @@ pub async fn add_answer(
         Err(e) => return Err(warp::reject::custom(e)),
     };
 
-    let content = match get_ai_content(question.content).await {
+    let answers = store.get_answers(id).await?;
+    let sources = knowledge_sources(&store, &question).await?;
+    let prompt = template.render(&question, &answers, &sources);
+    let cited = prompt.sources.clone();
+
+    let content = match ai.generate(prompt).await {
         Ok(res) => res,
         Err(e) => return Err(warp::reject::custom(e)),
     };
@@ -119,11 +247,37 @@ pub async fn add_answer(
     let answer = NewAnswer {
         content,
         question_id: QuestionId(id),
+        prompt_version: Some(template.version.clone()),
     };
 
 
     match store.add_answer(answer, account_id).await {
-        Ok(_) => Ok(warp::reply::with_status("Answer added", StatusCode::OK)),
+        Ok(answer) => Ok(warp::reply::json(&GeneratedAnswer {
+            answer,
+            sources: cited,
+        })),
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
+
+/// Answered questions similar to `question` from the search index, each with
+/// the answer the model may reuse
+async fn knowledge_sources(store: &Repository, question: &Question) -> Result<Vec<KnowledgeSource>, Error> {
+    let terms = retrieval_terms(question);
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Hits are questions and answers, so a few more than needed leave room for
+    // duplicates and for questions nobody answered yet
+    let mut related = Vec::new();
+    for result in store.search_any(&terms, (MAX_SOURCES * 2) as i64).await? {
+        if result.question_id != question.id && !related.contains(&result.question_id) {
+            related.push(result.question_id);
+        }
+    }
+    // Generated answers only count once accepted, the model shouldn't build on its own guesses
+    let mut sources = store.get_knowledge_sources(&related).await?;
+    sources.truncate(MAX_SOURCES);
+    Ok(sources)
+}
@@ -0,0 +1,17 @@
+use std::collections::HashMap;
+
+use crate::repositories::repository::Repository;
+use crate::types::search::extract_search;
+
+
+pub async fn search(
+    params: HashMap<String, String>,
+    store: Repository,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let query = extract_search(params)?;
+
+    match store.search(query.q, query.limit).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,9 @@
+use crate::repositories::repository::Repository;
+
+
+pub async fn get_tags(store: Repository) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_tags().await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
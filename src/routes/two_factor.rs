@@ -0,0 +1,99 @@
+use chrono::Utc;
+use warp::http::StatusCode;
+
+use crate::errors::Error;
+use crate::repositories::repository::Repository;
+use crate::types::account::{AccountId, Session};
+use crate::types::token::hash_token;
+use crate::types::totp::{
+    generate_recovery_codes, generate_secret, normalize_recovery_code, otpauth_uri, verify_code, RecoveryCodes,
+    SecondFactor, TotpConfirmation, TotpEnrollment,
+};
+
+/// Issuer shown next to the account in authenticator apps
+const TOTP_ISSUER: &str = "rush";
+
+/// Hands out a new secret. It only protects the account once a code from it
+/// was confirmed through `/2fa/confirm`, so enrolling again simply starts over.
+pub async fn enroll(session: Session, store: Repository) -> Result<impl warp::Reply, warp::Rejection> {
+    let account = store.get_account_by_id(session.account_id.clone()).await?;
+    let secret = generate_secret();
+    if !store.set_totp_secret(session.account_id, secret.clone()).await? {
+        return Err(warp::reject::custom(Error::InvalidParameter(
+            "two-factor authentication is already enabled".to_string(),
+        )));
+    }
+
+    Ok(warp::reply::json(&TotpEnrollment {
+        otpauth_uri: otpauth_uri(TOTP_ISSUER, &account.email, &secret),
+        secret,
+    }))
+}
+
+/// Enables two-factor authentication with the first code from the app and
+/// returns the recovery codes, which can't be shown again
+pub async fn confirm(
+    session: Session,
+    store: Repository,
+    confirmation: TotpConfirmation,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let totp = store.get_totp(session.account_id.clone()).await?;
+    if totp.is_enabled() {
+        return Err(warp::reject::custom(Error::InvalidParameter(
+            "two-factor authentication is already enabled".to_string(),
+        )));
+    }
+    let step = verify_code(&totp.secret, &confirmation.code, Utc::now().timestamp(), totp.last_used_step)
+        .ok_or(Error::WrongSecondFactor)?;
+
+    let recovery_codes = generate_recovery_codes();
+    let hashes = recovery_codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect();
+    if !store.enable_totp(session.account_id, step, hashes).await? {
+        return Err(warp::reject::custom(Error::WrongSecondFactor));
+    }
+
+    Ok(warp::reply::json(&RecoveryCodes { recovery_codes }))
+}
+
+/// Turns two-factor authentication off, which takes a current code or a
+/// recovery code so a stolen session alone isn't enough
+pub async fn disable(
+    session: Session,
+    store: Repository,
+    factor: SecondFactor,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !check_second_factor(&store, &session.account_id, &factor).await? {
+        return Err(warp::reject::custom(Error::WrongSecondFactor));
+    }
+
+    match store.disable_totp(session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status("Two-factor authentication disabled", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Checks a code from the app or uses up a recovery code. Accounts without
+/// two-factor authentication never pass.
+pub async fn check_second_factor(store: &Repository, account_id: &AccountId, factor: &SecondFactor) -> Result<bool, Error> {
+    let totp = match store.get_totp(account_id.clone()).await {
+        Ok(totp) if totp.is_enabled() => totp,
+        Ok(_) | Err(Error::NotFound) => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    match (&factor.code, &factor.recovery_code) {
+        (Some(code), _) => match verify_code(&totp.secret, code, Utc::now().timestamp(), totp.last_used_step) {
+            Some(step) => store.use_totp_step(account_id.clone(), step).await,
+            None => Ok(false),
+        },
+        (None, Some(recovery_code)) => {
+            store
+                .use_recovery_code(account_id.clone(), hash_token(&normalize_recovery_code(recovery_code)))
+                .await
+        }
+        (None, None) => Ok(false),
+    }
+}
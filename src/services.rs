@@ -1 +1,6 @@
-pub mod google_ai_service;
\ No newline at end of file
+pub mod ai_provider;
+pub mod google_ai_service;
+pub mod mailer;
+pub mod oidc;
+pub mod ollama_service;
+pub mod openai_service;
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+
+use crate::errors::Error;
+use crate::types::prompt::Prompt;
+
+/// Language model that writes the generated answers
+#[async_trait]
+pub trait AiProvider {
+    async fn generate(&self, prompt: Prompt) -> Result<String, Error>;
+}
+
+pub type SharedAiProvider = Arc<dyn AiProvider + Send + Sync>;
+
+/// HTTP client of the remote providers, retrying transient failures. The
+/// timeout applies to every attempt.
+pub fn retrying_client(timeout: Duration) -> Result<ClientWithMiddleware, Error> {
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(Error::ReqwestAPIError)?;
+    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
+    Ok(ClientBuilder::new(client)
+        // Retry failed requests.
+        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+        .build())
+}
+
+/// Answers without calling any model, for tests and local development
+/// # Example usage
+/// ```rust
+/// use rush::services::ai_provider::{AiProvider, FakeAiProvider};
+/// use rush::types::prompt::Prompt;
+///
+/// let provider = FakeAiProvider::new();
+/// let prompt = Prompt {
+///     system: "Be brief.".to_string(),
+///     user: "What is Rust?".to_string(),
+///     sources: Vec::new(),
+/// };
+/// let answer = tokio::runtime::Runtime::new()
+///     .unwrap()
+///     .block_on(provider.generate(prompt))
+///     .unwrap();
+/// assert_eq!(answer, "Generated answer to: What is Rust?");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakeAiProvider;
+
+impl FakeAiProvider {
+    pub fn new() -> FakeAiProvider {
+        FakeAiProvider
+    }
+}
+
+#[async_trait]
+impl AiProvider for FakeAiProvider {
+    async fn generate(&self, prompt: Prompt) -> Result<String, Error> {
+        Ok(format!("Generated answer to: {}", prompt.user))
+    }
+}
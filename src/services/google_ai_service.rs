@@ -1,11 +1,12 @@
-use std::env;
+use std::time::Duration;
 
-use reqwest_middleware::ClientBuilder;
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use async_trait::async_trait;
+use reqwest_middleware::ClientWithMiddleware;
 use serde::{Deserialize, Serialize};
-use crate::config;
 
 use crate::errors::{APILayerError, Error};
+use crate::services::ai_provider::{retrying_client, AiProvider};
+use crate::types::prompt::Prompt;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -24,18 +25,19 @@ pub struct ErrorMsg {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GoogleAIRequest {
+    system_instruction: Content,
     contents: Vec<Content>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct GoogleAIResponse {
     pub candidates: Vec<Candidate>,
     pub prompt_feedback: PromptFeedback,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", default)]
 pub struct Candidate {
     pub content: Content,
     pub finish_reason: String,
@@ -69,52 +71,74 @@ pub struct PromptFeedback {
     pub safety_ratings: Vec<SafetyRating>,
 }
 
-pub async fn get_ai_content(content: String) -> Result<String, Error> {
-    // We are already checking if the ENV VARIABLE is set inside main.rs, so safe to unwrap here
-    let api_key = env::var(config::GOOGLE_AI_KEY).unwrap();
-
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-    let client = ClientBuilder::new(reqwest::Client::new())
-        // Trace HTTP requests. See the tracing crate to make use of these traces.
-        // Retry failed requests.
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build();
-
-    let res = client
-        .post(
-            format!("https://generativelanguage.googleapis.com/v1beta/models/gemini-pro:generateContent?key={}",api_key),
-        )
-        .json(&GoogleAIRequest{
-            contents: vec![Content{
-                parts: vec![Part{
-                    text: content.to_string(),
-                }],
-                role: "".to_string(),
-            }],
+/// Gemini through the Generative Language API
+pub struct GeminiProvider {
+    /// e.g. `https://generativelanguage.googleapis.com/v1beta`
+    base_url: String,
+    model: String,
+    api_key: String,
+    client: ClientWithMiddleware,
+}
+
+impl GeminiProvider {
+    pub fn new(base_url: &str, model: &str, api_key: &str, timeout: Duration) -> Result<GeminiProvider, Error> {
+        Ok(GeminiProvider {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key: api_key.to_string(),
+            client: retrying_client(timeout)?,
         })
-        .send()
-        .await
-        .map_err(Error::MiddlewareReqwestAPIError)?;
-
-    if !res.status().is_success() {
-        if res.status().is_client_error() {
-            let err = transform_error(res).await;
-            return Err(Error::ClientError(err));
-        } else {
-            let err = transform_error(res).await;
-            return Err(Error::ServerError(err));
-        }
     }
+}
+
+#[async_trait]
+impl AiProvider for GeminiProvider {
+    async fn generate(&self, prompt: Prompt) -> Result<String, Error> {
+        let res = self
+            .client
+            .post(format!("{}/models/{}:generateContent", self.base_url, self.model))
+            .header("x-goog-api-key", &self.api_key)
+            .json(&GoogleAIRequest {
+                system_instruction: Content {
+                    parts: vec![Part { text: prompt.system }],
+                    role: "".to_string(),
+                },
+                contents: vec![Content {
+                    parts: vec![Part { text: prompt.user }],
+                    role: "user".to_string(),
+                }],
+            })
+            .send()
+            .await
+            .map_err(Error::MiddlewareReqwestAPIError)?;
+
+        if !res.status().is_success() {
+            if res.status().is_client_error() {
+                let err = transform_error(res).await;
+                return Err(Error::ClientError(err));
+            } else {
+                let err = transform_error(res).await;
+                return Err(Error::ServerError(err));
+            }
+        }
 
-    match res.json::<GoogleAIResponse>().await {
-        Ok(res) => Ok(res.candidates[0].content.parts[0].text.clone()),
-        Err(e) => Err(Error::ReqwestAPIError(e)),
+        let res = res.json::<GoogleAIResponse>().await.map_err(Error::ReqwestAPIError)?;
+        // Blocked prompts come back without candidates
+        match res.candidates.first().and_then(|candidate| candidate.content.parts.first()) {
+            Some(part) => Ok(part.text.clone()),
+            None => Err(Error::ServerError(APILayerError {
+                status: 502,
+                message: format!("{} returned no answer", self.model),
+            })),
+        }
     }
 }
 
 async fn transform_error(res: reqwest::Response) -> APILayerError {
-    APILayerError {
-        status: res.status().as_u16(),
-        message: res.json::<APIResponse>().await.unwrap().error.message,
-    }
+    let status = res.status().as_u16();
+    let message = match res.json::<APIResponse>().await {
+        Ok(body) => body.error.message,
+        Err(e) => e.to_string(),
+    };
+    APILayerError { status, message }
 }
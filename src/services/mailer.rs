@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use clap::ValueEnum;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use tokio::io::AsyncWriteExt;
+
+use crate::errors::Error;
+
+/// Mails are plain text, the links in them point to the web app
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer {
+    async fn send(&self, email: Email) -> Result<(), Error>;
+}
+
+pub type SharedMailer = Arc<dyn Mailer + Send + Sync>;
+
+fn message(from: &Mailbox, email: Email) -> Result<Message, Error> {
+    let to: Mailbox = email
+        .to
+        .parse()
+        .map_err(|e| Error::MailError(format!("invalid recipient {}: {}", email.to, e)))?;
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(email.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(email.body)
+        .map_err(|e| Error::MailError(e.to_string()))
+}
+
+pub fn parse_mailbox(mailbox: &str) -> Result<Mailbox, Error> {
+    mailbox
+        .parse()
+        .map_err(|e| Error::MailError(format!("invalid sender {}: {}", mailbox, e)))
+}
+
+/// How the connection to the SMTP server is secured
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[clap(rename_all = "kebab_case")]
+pub enum SmtpSecurity {
+    /// Plain text, only for local catchers like MailHog or Mailpit
+    None,
+    Starttls,
+    Tls,
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        host: &str,
+        port: u16,
+        security: SmtpSecurity,
+        credentials: Option<(String, String)>,
+        from: Mailbox,
+    ) -> Result<SmtpMailer, Error> {
+        let builder = match security {
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| Error::MailError(e.to_string()))?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .map_err(|e| Error::MailError(e.to_string()))?,
+        };
+        let builder = match credentials {
+            Some((username, password)) => builder.credentials(Credentials::new(username, password)),
+            None => builder,
+        };
+
+        Ok(SmtpMailer {
+            transport: builder.port(port).build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), Error> {
+        self.transport
+            .send(message(&self.from, email)?)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::MailError(e.to_string()))
+    }
+}
+
+/// Appends mails to a file or writes them to stdout, for local development
+/// and tests. Unlike on the wire the body isn't encoded, so links can be
+/// copied as they are.
+pub struct FileMailer {
+    /// Stdout when `None`
+    path: Option<String>,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(path: Option<String>, from: Mailbox) -> FileMailer {
+        FileMailer { path, from }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), Error> {
+        let formatted = format!(
+            "From: {}\nTo: {}\nSubject: {}\n\n{}\n\n",
+            self.from, email.to, email.subject, email.body
+        );
+
+        let written = match &self.path {
+            Some(path) => {
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .map_err(|e| Error::MailError(format!("cannot open {}: {}", path, e)))?;
+                file.write_all(formatted.as_bytes()).await
+            }
+            None => tokio::io::stdout().write_all(formatted.as_bytes()).await,
+        };
+        written.map_err(|e| Error::MailError(e.to_string()))
+    }
+}
+
+/// The mails sent to account owners, with links into the web app at `app_url`
+#[derive(Clone)]
+pub struct AccountMailer {
+    mailer: SharedMailer,
+    app_url: String,
+}
+
+impl AccountMailer {
+    pub fn new(mailer: SharedMailer, app_url: &str) -> AccountMailer {
+        AccountMailer {
+            mailer,
+            app_url: app_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub async fn send_verification(&self, to: &str, token: &str, valid_hours: i64) -> Result<(), Error> {
+        self.mailer
+            .send(Email {
+                to: to.to_string(),
+                subject: "Confirm your email address".to_string(),
+                body: format!(
+                    "Welcome to rush!\n\nConfirm your email address by opening\n{}/verify-email?token={}\n\nThe link expires in {}.\n",
+                    self.app_url, token, hours(valid_hours)
+                ),
+            })
+            .await
+    }
+
+    pub async fn send_password_reset(&self, to: &str, token: &str, valid_hours: i64) -> Result<(), Error> {
+        self.mailer
+            .send(Email {
+                to: to.to_string(),
+                subject: "Reset your password".to_string(),
+                body: format!(
+                    "Someone asked to reset the password of your rush account.\n\nChoose a new password at\n{}/reset-password?token={}\n\nThe link expires in {} and works once. If it wasn't you, ignore this mail.\n",
+                    self.app_url, token, hours(valid_hours)
+                ),
+            })
+            .await
+    }
+}
+
+fn hours(hours: i64) -> String {
+    match hours {
+        1 => "1 hour".to_string(),
+        n => format!("{} hours", n),
+    }
+}
@@ -0,0 +1,149 @@
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use reqwest::Url;
+
+use crate::errors::Error;
+use crate::types::oidc::{IdTokenClaims, ProviderMetadata, ProviderTokens};
+
+/// Relying party of the authorization code flow with PKCE. The provider
+/// metadata and its keys are fetched on every login, so key rotation at the
+/// provider needs no restart.
+pub struct OidcClient {
+    issuer: String,
+    client_id: String,
+    /// Empty for public clients, which only rely on PKCE
+    client_secret: String,
+    redirect_url: String,
+    /// Whether ID tokens without `email_verified` may create new accounts,
+    /// existing accounts are only ever linked with a verified email
+    provision_unverified: bool,
+    http: reqwest::Client,
+}
+
+impl OidcClient {
+    pub fn new(
+        issuer: &str,
+        client_id: &str,
+        client_secret: &str,
+        redirect_url: &str,
+        provision_unverified: bool,
+    ) -> OidcClient {
+        OidcClient {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            redirect_url: redirect_url.to_string(),
+            provision_unverified,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn provision_unverified(&self) -> bool {
+        self.provision_unverified
+    }
+
+    pub async fn provider_metadata(&self) -> Result<ProviderMetadata, Error> {
+        let metadata: ProviderMetadata = self
+            .get_json(&format!("{}/.well-known/openid-configuration", self.issuer))
+            .await?;
+        if metadata.issuer.trim_end_matches('/') != self.issuer {
+            return Err(Error::OidcError(format!("provider claims to be {}", metadata.issuer)));
+        }
+        Ok(metadata)
+    }
+
+    /// Where the browser signs in, asking for the email along with the ID token
+    pub fn authorization_url(
+        &self,
+        metadata: &ProviderMetadata,
+        state: &str,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<Url, Error> {
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| Error::OidcError(format!("invalid authorization endpoint: {}", e)))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_url)
+            .append_pair("scope", "openid email")
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url)
+    }
+
+    /// Trades the code from the redirect for tokens, authenticating with
+    /// `client_secret_basic` unless this is a public client
+    pub async fn exchange_code(
+        &self,
+        metadata: &ProviderMetadata,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<ProviderTokens, Error> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_url),
+            ("code_verifier", code_verifier),
+        ];
+        let request = self.http.post(&metadata.token_endpoint);
+        let request = if self.client_secret.is_empty() {
+            form.push(("client_id", &self.client_id));
+            request
+        } else {
+            request.basic_auth(&self.client_id, Some(&self.client_secret))
+        };
+
+        let res = request.form(&form).send().await.map_err(Error::ReqwestAPIError)?;
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            return Err(Error::OidcError(format!("token endpoint answered {}: {}", status, body)));
+        }
+        res.json().await.map_err(Error::ReqwestAPIError)
+    }
+
+    /// Checks signature, issuer, audience, expiry and nonce of the ID token
+    pub async fn verify_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, Error> {
+        let header = decode_header(id_token).map_err(|e| Error::OidcError(format!("invalid ID token: {}", e)))?;
+        // Shared-secret algorithms would let anyone holding the client secret mint tokens
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(Error::OidcError(format!("ID token signed with {:?}", header.alg)));
+        }
+
+        let keys: JwkSet = self.get_json(&metadata.jwks_uri).await?;
+        let jwk = match &header.kid {
+            Some(kid) => keys.find(kid),
+            None => keys.keys.first(),
+        }
+        .ok_or_else(|| Error::OidcError("ID token signed with an unknown key".to_string()))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| Error::OidcError(format!("unusable provider key: {}", e)))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_issuer(&[&self.issuer, &metadata.issuer]);
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| Error::OidcError(format!("invalid ID token: {}", e)))?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(Error::OidcError("ID token for another login".to_string()));
+        }
+        Ok(claims)
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, Error> {
+        let res = self.http.get(url).send().await.map_err(Error::ReqwestAPIError)?;
+        if !res.status().is_success() {
+            return Err(Error::OidcError(format!("{} answered {}", url, res.status())));
+        }
+        res.json().await.map_err(Error::ReqwestAPIError)
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{APILayerError, Error};
+use crate::services::ai_provider::{retrying_client, AiProvider};
+use crate::types::prompt::Prompt;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct APIResponse {
+    pub error: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<Message>,
+    /// One response with the whole answer instead of a stream of chunks
+    stream: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub message: Message,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+}
+
+/// Model served by a local Ollama, so questions never leave the network
+pub struct OllamaProvider {
+    /// e.g. `http://localhost:11434`
+    base_url: String,
+    model: String,
+    client: ClientWithMiddleware,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: &str, model: &str, timeout: Duration) -> Result<OllamaProvider, Error> {
+        Ok(OllamaProvider {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            client: retrying_client(timeout)?,
+        })
+    }
+}
+
+#[async_trait]
+impl AiProvider for OllamaProvider {
+    async fn generate(&self, prompt: Prompt) -> Result<String, Error> {
+        let res = self
+            .client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&ChatRequest {
+                model: self.model.clone(),
+                messages: vec![
+                    Message {
+                        role: "system".to_string(),
+                        content: prompt.system,
+                    },
+                    Message {
+                        role: "user".to_string(),
+                        content: prompt.user,
+                    },
+                ],
+                stream: false,
+            })
+            .send()
+            .await
+            .map_err(Error::MiddlewareReqwestAPIError)?;
+
+        if !res.status().is_success() {
+            if res.status().is_client_error() {
+                let err = transform_error(res).await;
+                return Err(Error::ClientError(err));
+            } else {
+                let err = transform_error(res).await;
+                return Err(Error::ServerError(err));
+            }
+        }
+
+        let res = res.json::<ChatResponse>().await.map_err(Error::ReqwestAPIError)?;
+        Ok(res.message.content)
+    }
+}
+
+async fn transform_error(res: reqwest::Response) -> APILayerError {
+    let status = res.status().as_u16();
+    let message = match res.json::<APIResponse>().await {
+        Ok(body) => body.error,
+        Err(e) => e.to_string(),
+    };
+    APILayerError { status, message }
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{APILayerError, Error};
+use crate::services::ai_provider::{retrying_client, AiProvider};
+use crate::types::prompt::Prompt;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct APIResponse {
+    pub error: ErrorMsg,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorMsg {
+    pub message: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ChatCompletionRequest {
+    model: String,
+    messages: Vec<Message>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatCompletionResponse {
+    #[serde(default)]
+    pub choices: Vec<Choice>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Choice {
+    pub message: Message,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: String,
+    /// Null when the model refused or only called tools
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+/// Any server speaking the OpenAI chat completions API, like OpenAI itself,
+/// vLLM or LM Studio
+pub struct OpenAiProvider {
+    /// e.g. `https://api.openai.com/v1`
+    base_url: String,
+    model: String,
+    /// Left empty for servers without authentication
+    api_key: String,
+    client: ClientWithMiddleware,
+}
+
+impl OpenAiProvider {
+    pub fn new(base_url: &str, model: &str, api_key: &str, timeout: Duration) -> Result<OpenAiProvider, Error> {
+        Ok(OpenAiProvider {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key: api_key.to_string(),
+            client: retrying_client(timeout)?,
+        })
+    }
+}
+
+#[async_trait]
+impl AiProvider for OpenAiProvider {
+    async fn generate(&self, prompt: Prompt) -> Result<String, Error> {
+        let request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&ChatCompletionRequest {
+                model: self.model.clone(),
+                messages: vec![
+                    Message {
+                        role: "system".to_string(),
+                        content: Some(prompt.system),
+                    },
+                    Message {
+                        role: "user".to_string(),
+                        content: Some(prompt.user),
+                    },
+                ],
+            });
+        let request = if self.api_key.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&self.api_key)
+        };
+        let res = request.send().await.map_err(Error::MiddlewareReqwestAPIError)?;
+
+        if !res.status().is_success() {
+            if res.status().is_client_error() {
+                let err = transform_error(res).await;
+                return Err(Error::ClientError(err));
+            } else {
+                let err = transform_error(res).await;
+                return Err(Error::ServerError(err));
+            }
+        }
+
+        let res = res.json::<ChatCompletionResponse>().await.map_err(Error::ReqwestAPIError)?;
+        match res.choices.into_iter().next().and_then(|choice| choice.message.content) {
+            Some(content) => Ok(content),
+            None => Err(Error::ServerError(APILayerError {
+                status: 502,
+                message: format!("{} returned no answer", self.model),
+            })),
+        }
+    }
+}
+
+async fn transform_error(res: reqwest::Response) -> APILayerError {
+    let status = res.status().as_u16();
+    let message = match res.json::<APIResponse>().await {
+        Ok(body) => body.error.message,
+        Err(e) => e.to_string(),
+    };
+    APILayerError { status, message }
+}
@@ -1,22 +1,50 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use chrono::Utc;
 use tokio::sync::RwLock;
 
-
-
+use crate::types::account::{Account, AccountId};
 use crate::types::answer::{Answer, AnswerId};
+use crate::types::api_key::ApiKey;
+use crate::types::comment::{Comment, CommentId};
+use crate::types::login_attempt::LoginFailures;
+use crate::types::moderation::ModerationEntry;
+use crate::types::oidc::OidcLogin;
 use crate::types::question::{Question, QuestionId};
-
-const DEFAULT_FILE_PATH: &str = "../questions.json";
+use crate::types::token::{AccountToken, RefreshToken};
+use crate::types::totp::Totp;
 
 #[derive(Debug, Clone)]
 pub struct MemoryStore {
     pub questions: Arc<RwLock<HashMap<QuestionId, Question>>>,
     pub answers: Arc<RwLock<HashMap<AnswerId, Answer>>>,
+    pub comments: Arc<RwLock<HashMap<CommentId, Comment>>>,
+    /// Accounts keyed by their (unique) email, like the primary key of the `accounts` table
+    pub accounts: Arc<RwLock<HashMap<String, Account>>>,
+    /// Vote value (+1/-1) per voter, like the `question_votes` table
+    pub question_votes: Arc<RwLock<HashMap<(QuestionId, AccountId), i32>>>,
+    /// Vote value (+1/-1) per voter, like the `answer_votes` table
+    pub answer_votes: Arc<RwLock<HashMap<(AnswerId, AccountId), i32>>>,
+    /// Append only, like the `moderation_log` table
+    pub moderation_log: Arc<RwLock<Vec<ModerationEntry>>>,
+    pub refresh_tokens: Arc<RwLock<HashMap<i32, RefreshToken>>>,
+    pub account_tokens: Arc<RwLock<HashMap<i32, AccountToken>>>,
+    pub totp: Arc<RwLock<HashMap<AccountId, Totp>>>,
+    /// Hashes of the unused recovery codes of each account
+    pub recovery_codes: Arc<RwLock<HashMap<AccountId, Vec<String>>>>,
+    pub login_failures: Arc<RwLock<HashMap<String, LoginFailures>>>,
+    /// Keyed by the hash of the key, like the unique `key_hash` column
+    pub api_keys: Arc<RwLock<HashMap<String, ApiKey>>>,
+    pub oidc_logins: Arc<RwLock<HashMap<String, OidcLogin>>>,
 
     pub question_index: Arc<RwLock<i32>>,
     pub answer_index: Arc<RwLock<i32>>,
+    pub comment_index: Arc<RwLock<i32>>,
+    pub account_index: Arc<RwLock<i32>>,
+    pub refresh_token_index: Arc<RwLock<i32>>,
+    pub account_token_index: Arc<RwLock<i32>>,
+    pub api_key_index: Arc<RwLock<i32>>,
 }
 
 impl Default for MemoryStore {
@@ -27,16 +55,44 @@ impl Default for MemoryStore {
 
 impl MemoryStore {
     pub fn new() -> Self {
+        let questions = Self::init();
+        // Continue numbering after the seeded questions so new ids never collide
+        let question_index = questions.keys().map(|id| id.0).max().unwrap_or(0) + 1;
+
         MemoryStore {
-            questions: Arc::new(RwLock::new(Self::init())),
+            questions: Arc::new(RwLock::new(questions)),
             answers: Arc::new(RwLock::new(HashMap::new())),
-            question_index: Arc::new(RwLock::new(1)),
+            comments: Arc::new(RwLock::new(HashMap::new())),
+            accounts: Arc::new(RwLock::new(HashMap::new())),
+            question_votes: Arc::new(RwLock::new(HashMap::new())),
+            answer_votes: Arc::new(RwLock::new(HashMap::new())),
+            moderation_log: Arc::new(RwLock::new(Vec::new())),
+            refresh_tokens: Arc::new(RwLock::new(HashMap::new())),
+            account_tokens: Arc::new(RwLock::new(HashMap::new())),
+            totp: Arc::new(RwLock::new(HashMap::new())),
+            recovery_codes: Arc::new(RwLock::new(HashMap::new())),
+            login_failures: Arc::new(RwLock::new(HashMap::new())),
+            api_keys: Arc::new(RwLock::new(HashMap::new())),
+            oidc_logins: Arc::new(RwLock::new(HashMap::new())),
+            question_index: Arc::new(RwLock::new(question_index)),
             answer_index: Arc::new(RwLock::new(1)),
+            comment_index: Arc::new(RwLock::new(1)),
+            account_index: Arc::new(RwLock::new(1)),
+            refresh_token_index: Arc::new(RwLock::new(1)),
+            account_token_index: Arc::new(RwLock::new(1)),
+            api_key_index: Arc::new(RwLock::new(1)),
         }
     }
 
     fn init() -> HashMap<QuestionId, Question> {
         let file = include_str!("../../questions.json");
-        serde_json::from_str(file).expect("can't read questions.json")
+        let mut questions: HashMap<QuestionId, Question> =
+            serde_json::from_str(file).expect("can't read questions.json");
+        // Listings page on `(created_on, id)`, so the seeded questions need a date too
+        let now = Utc::now().naive_utc();
+        for question in questions.values_mut() {
+            question.created_on.get_or_insert(now);
+        }
+        questions
     }
-}
\ No newline at end of file
+}
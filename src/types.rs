@@ -1,4 +1,17 @@
 pub mod account;
 pub mod answer;
+pub mod api_key;
+pub mod capabilities;
+pub mod comment;
+pub mod key_ring;
+pub mod login_attempt;
+pub mod moderation;
+pub mod oidc;
 pub mod pagination;
+pub mod prompt;
 pub mod question;
+pub mod search;
+pub mod tag;
+pub mod token;
+pub mod totp;
+pub mod vote;
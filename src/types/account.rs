@@ -1,11 +1,52 @@
+use std::str::FromStr;
+
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::errors::Error;
+use crate::types::answer::Answer;
+use crate::types::api_key::{ApiKeyGrant, Scope};
+use crate::types::pagination::Page;
+use crate::types::question::Question;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Session {
     pub exp: DateTime<Utc>,
     pub account_id: AccountId,
     pub nbf: DateTime<Utc>,
+    /// Id of the refresh token row the access token belongs to, see [`crate::types::token::RefreshToken`].
+    /// 0 for API keys.
+    pub session_id: i32,
+    /// Role at the time the token was issued, tokens from before roles existed are `user`
+    #[serde(default)]
+    pub role: Role,
+    /// Whether the login passed two-factor authentication
+    #[serde(default)]
+    pub mfa: bool,
+    /// Set when the request came with an API key instead of an access token
+    #[serde(default)]
+    pub api_key: Option<ApiKeyGrant>,
+}
+
+impl Session {
+    /// Logins may do everything, API keys only what their scopes cover
+    /// # Example usage
+    /// ```rust
+    /// use chrono::Utc;
+    /// use rush::types::account::{AccountId, Role, Session};
+    /// use rush::types::api_key::{ApiKeyGrant, ApiKeyId, Scope};
+    ///
+    /// let session = Session { exp: Utc::now(), nbf: Utc::now(), account_id: AccountId(1), session_id: 1, role: Role::User, mfa: false, api_key: None };
+    /// assert!(session.allows(Scope::VotesWrite));
+    ///
+    /// let grant = ApiKeyGrant { id: ApiKeyId(1), scopes: vec![Scope::AnswersWrite] };
+    /// let session = Session { session_id: 0, api_key: Some(grant), ..session };
+    /// assert!(session.allows(Scope::AnswersWrite));
+    /// assert!(!session.allows(Scope::VotesWrite));
+    /// ```
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.api_key.as_ref().map(|grant| grant.scopes.contains(&scope)).unwrap_or(true)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -13,7 +54,257 @@ pub struct Account {
     pub id: Option<AccountId>,
     pub email: String,
     pub password: String,
+    /// Set by the repository and ignored when sent by a client
+    #[serde(default)]
+    pub role: Role,
+    /// Set once the owner followed the link mailed on registration, ignored when sent by a client
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub bio: Option<String>,
+    /// Set by the repository and ignored when sent by a client
+    #[serde(default)]
+    pub created_on: Option<NaiveDateTime>,
+}
+
+/// What anyone may see of an account
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Profile {
+    pub id: AccountId,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub role: Role,
+    pub created_on: Option<NaiveDateTime>,
+}
+
+impl From<&Account> for Profile {
+    fn from(account: &Account) -> Self {
+        Profile {
+            id: account.id.clone().expect("id not found"),
+            display_name: account.display_name.clone(),
+            avatar_url: account.avatar_url.clone(),
+            bio: account.bio.clone(),
+            role: account.role,
+            created_on: account.created_on,
+        }
+    }
+}
+
+/// Returned by `GET /accounts/{id}`, with the newest questions and answers of the account
+#[derive(Serialize, Debug, Clone)]
+pub struct PublicProfile {
+    #[serde(flatten)]
+    pub profile: Profile,
+    /// First page of `/questions?author={id}&sort=newest`
+    pub questions: Page<Question>,
+    pub answers: Vec<Answer>,
+}
+
+/// Returned by `GET /me` and `PUT /me`
+#[derive(Serialize, Debug, Clone)]
+pub struct OwnProfile {
+    #[serde(flatten)]
+    pub profile: Profile,
+    pub email: String,
+    pub email_verified: bool,
+}
+
+impl From<&Account> for OwnProfile {
+    fn from(account: &Account) -> Self {
+        OwnProfile {
+            profile: Profile::from(account),
+            email: account.email.clone(),
+            email_verified: account.email_verified,
+        }
+    }
+}
+
+/// Body of `PUT /me`. Replaces the whole profile, fields left out are cleared.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ProfileUpdate {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    /// Only needed to change the password
+    pub current_password: Option<String>,
+    pub new_password: Option<String>,
+}
+
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+pub const MAX_AVATAR_URL_LENGTH: usize = 2048;
+pub const MAX_BIO_LENGTH: usize = 2000;
+
+impl ProfileUpdate {
+    /// Trims the fields, turning blank ones into `None`, and checks their length.
+    /// Avatars have to be http(s) URLs, so they can't run scripts in the web app.
+    /// # Example usage
+    /// ```rust
+    /// use rush::types::account::ProfileUpdate;
+    ///
+    /// let update = ProfileUpdate {
+    ///     display_name: Some(" Jane ".to_string()),
+    ///     bio: Some("  ".to_string()),
+    ///     ..Default::default()
+    /// };
+    /// let update = update.normalized().unwrap();
+    /// assert_eq!(update.display_name.as_deref(), Some("Jane"));
+    /// assert_eq!(update.bio, None);
+    ///
+    /// let update = ProfileUpdate { avatar_url: Some("javascript:alert(1)".to_string()), ..Default::default() };
+    /// assert!(update.normalized().is_err());
+    /// ```
+    pub fn normalized(self) -> Result<ProfileUpdate, Error> {
+        let trimmed = |field: Option<String>| {
+            field
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let update = ProfileUpdate {
+            display_name: trimmed(self.display_name),
+            avatar_url: trimmed(self.avatar_url),
+            bio: trimmed(self.bio),
+            ..self
+        };
+
+        let too_long = |field: &Option<String>, max: usize| field.as_ref().map(|v| v.chars().count() > max).unwrap_or(false);
+        if too_long(&update.display_name, MAX_DISPLAY_NAME_LENGTH) {
+            return Err(Error::InvalidParameter("display_name".to_string()));
+        }
+        if too_long(&update.bio, MAX_BIO_LENGTH) {
+            return Err(Error::InvalidParameter("bio".to_string()));
+        }
+        if let Some(avatar_url) = &update.avatar_url {
+            let is_http = avatar_url.starts_with("https://") || avatar_url.starts_with("http://");
+            if !is_http || avatar_url.len() > MAX_AVATAR_URL_LENGTH || avatar_url.contains(char::is_whitespace) {
+                return Err(Error::InvalidParameter("avatar_url".to_string()));
+            }
+        }
+        Ok(update)
+    }
+}
+
+/// Body of `POST /password/forgot` and `POST /email/verification`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct EmailRequest {
+    pub email: String,
+}
+
+/// Cheap sanity check on registration, whether the address really exists is
+/// only known once the verification mail was received
+/// # Example usage
+/// ```rust
+/// use rush::types::account::is_valid_email;
+///
+/// assert!(is_valid_email("jane.doe@example.com"));
+/// assert!(!is_valid_email("jane.doe"));
+/// assert!(!is_valid_email("jane@localhost"));
+/// assert!(!is_valid_email("jane doe@example.com"));
+/// assert!(!is_valid_email("@example.com"));
+/// ```
+pub fn is_valid_email(email: &str) -> bool {
+    if email.len() > 254 || email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+        }
+        None => false,
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AccountId(pub i32);
+
+/// Accounts that become admins when they log in with a verified email, set
+/// with `ADMIN_EMAILS`. Bootstraps the first admin, who hands out all other roles.
+/// # Example usage
+/// ```rust
+/// use rush::types::account::AdminEmails;
+///
+/// let admins = AdminEmails::parse(" Root@Example.com, ops@example.com,");
+/// assert!(admins.contains("root@example.com"));
+/// assert!(admins.contains("OPS@example.com "));
+/// assert!(!admins.contains("jane@example.com"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AdminEmails(Vec<String>);
+
+impl AdminEmails {
+    /// Emails separated by commas
+    pub fn parse(emails: &str) -> AdminEmails {
+        AdminEmails(
+            emails
+                .split(',')
+                .map(|email| email.trim().to_lowercase())
+                .filter(|email| !email.is_empty())
+                .collect(),
+        )
+    }
+
+    pub fn contains(&self, email: &str) -> bool {
+        self.0.contains(&email.trim().to_lowercase())
+    }
+}
+
+/// What an account is allowed to do, every role includes the ones before it
+/// # Example usage
+/// ```rust
+/// use rush::types::account::Role;
+///
+/// assert!(Role::Admin >= Role::Moderator);
+/// assert!(Role::Agent < Role::Moderator);
+/// assert_eq!("moderator".parse::<Role>().unwrap(), Role::Moderator);
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    /// Support staff answering on behalf of the site
+    Agent,
+    /// Can edit and delete any question or answer
+    Moderator,
+    /// Can also hand out roles
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Agent => "agent",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Role::User),
+            "agent" => Ok(Role::Agent),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            other => Err(Error::InvalidParameter(format!("role={}", other))),
+        }
+    }
+}
+
+/// Body of `PUT /accounts/{id}/role`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RoleUpdate {
+    pub role: Role,
+}
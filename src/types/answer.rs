@@ -1,5 +1,7 @@
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
+use crate::types::account::AccountId;
 use crate::types::question::QuestionId;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -7,13 +9,38 @@ pub struct Answer {
     pub id: AnswerId,
     pub content: String,
     pub question_id: QuestionId,
+    /// The author, set by the repository and ignored when sent by a client
+    #[serde(default)]
+    pub account_id: Option<AccountId>,
+    /// Set by the repository and ignored when sent by a client
+    #[serde(default)]
+    pub created_on: Option<NaiveDateTime>,
+    /// Sum of all up (+1) and down (-1) votes, maintained by the repository
+    #[serde(default)]
+    pub score: i32,
+    /// Version of the [`crate::types::prompt::PromptTemplate`] that generated
+    /// the answer, `None` for answers people wrote
+    #[serde(default)]
+    pub prompt_version: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AnswerId(pub i32);
 
+/// Response of `POST /questions/{id}/answer`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GeneratedAnswer {
+    #[serde(flatten)]
+    pub answer: Answer,
+    /// Questions of the knowledge base the model was given to cite
+    pub sources: Vec<QuestionId>,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct NewAnswer {
     pub content: String,
     pub question_id: QuestionId,
+    /// Only set for generated answers, never taken from a client
+    #[serde(skip)]
+    pub prompt_version: Option<String>,
 }
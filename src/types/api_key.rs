@@ -0,0 +1,141 @@
+use std::str::FromStr;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::NaiveDateTime;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::types::account::AccountId;
+
+/// Every API key starts with this, so `auth` can tell them from access tokens
+pub const API_KEY_PREFIX: &str = "rush_";
+/// Characters of a key kept in clear, so owners can tell their keys apart
+pub const API_KEY_HINT_LENGTH: usize = 12;
+/// Longest lifetime a key can be created with, keys may also never expire
+pub const MAX_API_KEY_DAYS: i64 = 3650;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ApiKeyId(pub i32);
+
+/// What an API key may do. There are deliberately no read scopes like
+/// `answers:read`: questions, answers, comments, tags and search are public,
+/// and the few reads that need a login (`/me`, `/api-keys`, the moderation
+/// log) take access tokens only, so a read scope would grant nothing.
+/// # Example usage
+/// ```rust
+/// use rush::types::api_key::Scope;
+///
+/// assert_eq!("answers:write".parse::<Scope>().unwrap(), Scope::AnswersWrite);
+/// assert_eq!(Scope::QuestionsWrite.as_str(), "questions:write");
+/// assert!("questions:delete".parse::<Scope>().is_err());
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Asking, editing and closing questions, and accepting answers
+    #[serde(rename = "questions:write")]
+    QuestionsWrite,
+    /// Answering, including the generated answers
+    #[serde(rename = "answers:write")]
+    AnswersWrite,
+    #[serde(rename = "comments:write")]
+    CommentsWrite,
+    #[serde(rename = "votes:write")]
+    VotesWrite,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::QuestionsWrite => "questions:write",
+            Scope::AnswersWrite => "answers:write",
+            Scope::CommentsWrite => "comments:write",
+            Scope::VotesWrite => "votes:write",
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "questions:write" => Ok(Scope::QuestionsWrite),
+            "answers:write" => Ok(Scope::AnswersWrite),
+            "comments:write" => Ok(Scope::CommentsWrite),
+            "votes:write" => Ok(Scope::VotesWrite),
+            other => Err(Error::InvalidParameter(format!("scope={}", other))),
+        }
+    }
+}
+
+/// Long-lived credential of a machine client, acting as the account that
+/// created it within its scopes. Like the `api_keys` table.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    pub account_id: AccountId,
+    pub name: String,
+    /// Start of the key, e.g. `rush_AbCdEfG`
+    pub hint: String,
+    pub scopes: Vec<Scope>,
+    pub created_on: NaiveDateTime,
+    pub expires_on: Option<NaiveDateTime>,
+    pub last_used_on: Option<NaiveDateTime>,
+    pub revoked_on: Option<NaiveDateTime>,
+}
+
+impl ApiKey {
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        self.revoked_on.is_none() && self.expires_on.map(|expires_on| expires_on > now).unwrap_or(true)
+    }
+}
+
+/// Body of `POST /api-keys`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Keys don't expire when left out, at most [`MAX_API_KEY_DAYS`]
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewApiKey {
+    pub account_id: AccountId,
+    pub name: String,
+    pub hint: String,
+    /// See [`crate::types::token::hash_token`], the key itself is never stored
+    pub key_hash: String,
+    pub scopes: Vec<Scope>,
+    pub expires_on: Option<NaiveDateTime>,
+}
+
+/// Returned once by `POST /api-keys`, afterwards only the hint is shown
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+/// What a request made with an API key may do, see [`crate::types::account::Session`]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ApiKeyGrant {
+    pub id: ApiKeyId,
+    pub scopes: Vec<Scope>,
+}
+
+/// 32 random bytes behind [`API_KEY_PREFIX`]
+/// # Example usage
+/// ```rust
+/// use rush::types::api_key::{generate_api_key, API_KEY_PREFIX};
+///
+/// let key = generate_api_key();
+/// assert!(key.starts_with(API_KEY_PREFIX));
+/// assert_eq!(key.len(), API_KEY_PREFIX.len() + 43);
+/// ```
+pub fn generate_api_key() -> String {
+    format!("{}{}", API_KEY_PREFIX, URL_SAFE_NO_PAD.encode(rand::thread_rng().gen::<[u8; 32]>()))
+}
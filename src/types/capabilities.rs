@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Response of `GET /capabilities`, so clients can hide what this deployment
+/// doesn't offer
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Whether `POST /questions/{id}/answer` can generate answers
+    pub ai_answers: bool,
+    /// Whether `GET /oidc/login` is set up
+    pub single_sign_on: bool,
+}
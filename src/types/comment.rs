@@ -0,0 +1,44 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::types::account::AccountId;
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+
+/// A short clarification attached to either a question or an answer
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Comment {
+    pub id: CommentId,
+    pub content: String,
+    /// Set when the comment belongs to a question
+    pub question_id: Option<QuestionId>,
+    /// Set when the comment belongs to an answer
+    pub answer_id: Option<AnswerId>,
+    pub account_id: Option<AccountId>,
+    pub created_on: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CommentId(pub i32);
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NewComment {
+    pub content: String,
+}
+
+/// What a comment is attached to, taken from the route it was posted on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommentTarget {
+    Question(QuestionId),
+    Answer(AnswerId),
+}
+
+impl CommentTarget {
+    /// Whether the given comment is attached to this target
+    pub fn matches(&self, comment: &Comment) -> bool {
+        match self {
+            CommentTarget::Question(id) => comment.question_id.as_ref() == Some(id),
+            CommentTarget::Answer(id) => comment.answer_id.as_ref() == Some(id),
+        }
+    }
+}
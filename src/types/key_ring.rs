@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use pasetors::keys::{AsymmetricPublicKey, AsymmetricSecretKey};
+use pasetors::paserk::{FormatAsPaserk, Id};
+use pasetors::version4::V4;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+
+/// Length of a v2.local key
+pub const KEY_LENGTH: usize = 32;
+
+/// Footer of every token we issue, naming the key it was encrypted with
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct KeyFooter {
+    pub kid: String,
+}
+
+/// Layout of the key file, keys are given as plain strings like `PASETO_KEY`.
+/// `secret_key` and `public_keys` are optional PASERKs, see [`KeyRing::with_public_keys`]
+/// ```json
+/// { "current": "2026-10", "keys": { "2026-10": "...", "2026-04": "..." },
+///   "secret_key": "k4.secret...", "public_keys": ["k4.public..."] }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct KeyFile {
+    pub current: String,
+    pub keys: HashMap<String, String>,
+    #[serde(default)]
+    pub secret_key: Option<String>,
+    #[serde(default)]
+    pub public_keys: Vec<String>,
+}
+
+/// A verification key as published on `/.well-known/paseto-keys`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PublishedKey {
+    /// PASERK id (`k4.pid.`) that tokens signed with this key carry in their footer
+    pub kid: String,
+    pub version: String,
+    pub purpose: String,
+    /// The key itself as a `k4.public.` PASERK
+    pub key: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PublishedKeys {
+    pub keys: Vec<PublishedKey>,
+}
+
+/// Keys for access tokens. New tokens use the current key, the others only
+/// keep already issued tokens valid until they expire.
+///
+/// With a signing key new tokens are `v4.public` instead of `v2.local`, so other
+/// services can check them with the published public keys alone.
+#[derive(Clone)]
+pub struct KeyRing {
+    current: String,
+    keys: HashMap<String, Vec<u8>>,
+    signing_key: Option<(Id, AsymmetricSecretKey<V4>)>,
+    public_keys: HashMap<String, AsymmetricPublicKey<V4>>,
+}
+
+// Keeps the keys out of the logs
+impl fmt::Debug for KeyRing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut ids: Vec<&String> = self.keys.keys().collect();
+        ids.sort();
+        f.debug_struct("KeyRing")
+            .field("current", &self.current)
+            .field("keys", &ids)
+            .field("signing", &self.signing_key.as_ref().map(|(id, _)| paserk(id)))
+            .field("public_keys", &self.public_keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl KeyRing {
+    /// # Example usage
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use rush::types::key_ring::KeyRing;
+    ///
+    /// let mut keys = HashMap::new();
+    /// keys.insert("new".to_string(), "RANDOM_WORDS_WINTER_MACINTOSH_PC".to_string());
+    /// keys.insert("old".to_string(), "RANDOM_WORDS_SUMMER_MACINTOSH_PC".to_string());
+    /// let ring = KeyRing::new("new", keys).unwrap();
+    /// assert_eq!(ring.current().0, "new");
+    /// assert!(ring.get("old").is_some());
+    ///
+    /// let mut short = HashMap::new();
+    /// short.insert("new".to_string(), "too short".to_string());
+    /// assert!(KeyRing::new("new", short).is_err());
+    /// ```
+    pub fn new(current: &str, keys: HashMap<String, String>) -> Result<KeyRing, Error> {
+        if let Some((id, _)) = keys.iter().find(|(_, key)| key.len() != KEY_LENGTH) {
+            return Err(Error::KeyRingError(format!("key {} must be {} bytes long", id, KEY_LENGTH)));
+        }
+        if !keys.contains_key(current) {
+            return Err(Error::KeyRingError(format!("current key {} is missing", current)));
+        }
+
+        Ok(KeyRing {
+            current: current.to_string(),
+            keys: keys.into_iter().map(|(id, key)| (id, key.into_bytes())).collect(),
+            signing_key: None,
+            public_keys: HashMap::new(),
+        })
+    }
+
+    /// Adds a `k4.secret` PASERK to sign new tokens with and `k4.public` PASERKs
+    /// of retired signing keys. Keys are named by their PASERK id.
+    /// # Example usage
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use pasetors::keys::{AsymmetricKeyPair, Generate};
+    /// use pasetors::paserk::FormatAsPaserk;
+    /// use pasetors::version4::V4;
+    /// use rush::types::key_ring::KeyRing;
+    ///
+    /// let pair = AsymmetricKeyPair::<V4>::generate().unwrap();
+    /// let mut secret = String::new();
+    /// pair.secret.fmt(&mut secret).unwrap();
+    ///
+    /// let mut keys = HashMap::new();
+    /// keys.insert("default".to_string(), "RANDOM_WORDS_WINTER_MACINTOSH_PC".to_string());
+    /// let ring = KeyRing::new("default", keys).unwrap();
+    /// assert!(ring.published_keys().keys.is_empty());
+    ///
+    /// let ring = ring.with_public_keys(Some(&secret), &[]).unwrap();
+    /// let published = ring.published_keys();
+    /// assert_eq!(published.keys.len(), 1);
+    /// assert!(published.keys[0].kid.starts_with("k4.pid."));
+    /// assert!(published.keys[0].key.starts_with("k4.public."));
+    /// assert!(ring.public_key(&published.keys[0].kid).is_some());
+    ///
+    /// assert!(ring.with_public_keys(Some("k4.secret.nope"), &[]).is_err());
+    /// ```
+    pub fn with_public_keys(mut self, secret_key: Option<&str>, public_keys: &[String]) -> Result<KeyRing, Error> {
+        for key in public_keys {
+            let key = AsymmetricPublicKey::<V4>::try_from(key.trim())
+                .map_err(|_| Error::KeyRingError("public keys must be k4.public PASERKs".to_string()))?;
+            self.public_keys.insert(paserk(&Id::from(&key)), key);
+        }
+
+        if let Some(secret_key) = secret_key {
+            let secret_key = AsymmetricSecretKey::<V4>::try_from(secret_key.trim())
+                .map_err(|_| Error::KeyRingError("secret key must be a k4.secret PASERK".to_string()))?;
+            let public_key = AsymmetricPublicKey::<V4>::try_from(&secret_key)
+                .map_err(|_| Error::KeyRingError("cannot derive the public key".to_string()))?;
+            let id = Id::from(&public_key);
+            self.public_keys.insert(paserk(&id), public_key);
+            self.signing_key = Some((id, secret_key));
+        }
+
+        Ok(self)
+    }
+
+    pub fn from_file(path: &str) -> Result<KeyRing, Error> {
+        let file = std::fs::read_to_string(path)
+            .map_err(|e| Error::KeyRingError(format!("cannot read {}: {}", path, e)))?;
+        let file: KeyFile = serde_json::from_str(&file)
+            .map_err(|e| Error::KeyRingError(format!("cannot parse {}: {}", path, e)))?;
+        KeyRing::new(&file.current, file.keys)?.with_public_keys(file.secret_key.as_deref(), &file.public_keys)
+    }
+
+    /// Id and key new tokens are encrypted with
+    pub fn current(&self) -> (&str, &[u8]) {
+        (&self.current, &self.keys[&self.current])
+    }
+
+    pub fn get(&self, id: &str) -> Option<&[u8]> {
+        self.keys.get(id).map(Vec::as_slice)
+    }
+
+    /// Id and key new tokens are signed with, if tokens are `v4.public`
+    pub fn signing_key(&self) -> Option<(&Id, &AsymmetricSecretKey<V4>)> {
+        self.signing_key.as_ref().map(|(id, key)| (id, key))
+    }
+
+    pub fn public_key(&self, id: &str) -> Option<&AsymmetricPublicKey<V4>> {
+        self.public_keys.get(id)
+    }
+
+    pub fn published_keys(&self) -> PublishedKeys {
+        let mut keys: Vec<PublishedKey> = self
+            .public_keys
+            .iter()
+            .map(|(kid, key)| PublishedKey {
+                kid: kid.clone(),
+                version: "v4".to_string(),
+                purpose: "public".to_string(),
+                key: paserk(key),
+            })
+            .collect();
+        keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+        PublishedKeys { keys }
+    }
+}
+
+fn paserk(key: &dyn FormatAsPaserk) -> String {
+    let mut paserk = String::new();
+    key.fmt(&mut paserk).expect("writing to a string");
+    paserk
+}
+
+/// Read the footer of a token without checking it, to find the key it needs.
+/// The footer is authenticated when the token is decrypted or its signature checked afterwards.
+/// # Example usage
+/// ```rust
+/// use rush::types::key_ring::read_footer;
+///
+/// // "v2.local.<payload>.<base64url of {"kid":"2026-10"}>"
+/// let token = "v2.local.AAAA.eyJraWQiOiIyMDI2LTEwIn0";
+/// let (raw, footer) = read_footer(token).unwrap();
+/// assert_eq!(raw, r#"{"kid":"2026-10"}"#);
+/// assert_eq!(footer.kid, "2026-10");
+/// assert!(read_footer("v2.local.AAAA").is_none());
+/// ```
+pub fn read_footer(token: &str) -> Option<(String, KeyFooter)> {
+    let footer = token.split('.').nth(3)?;
+    let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(footer).ok()?).ok()?;
+    let footer = serde_json::from_str(&raw).ok()?;
+    Some((raw, footer))
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+
+/// What failed logins are counted for. Emails are counted whether an account
+/// exists or not, so a lockout tells nothing about which emails are registered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginKey {
+    Email(String),
+    Ip(IpAddr),
+}
+
+/// Key of the row in the `login_failures` table
+/// # Example usage
+/// ```rust
+/// use rush::types::login_attempt::LoginKey;
+///
+/// assert_eq!(LoginKey::Email(" Jane@Example.com".to_string()).to_string(), "email:jane@example.com");
+/// assert_eq!(LoginKey::Ip("127.0.0.1".parse().unwrap()).to_string(), "ip:127.0.0.1");
+/// ```
+impl fmt::Display for LoginKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoginKey::Email(email) => write!(f, "email:{}", email.trim().to_lowercase()),
+            LoginKey::Ip(ip) => write!(f, "ip:{}", ip),
+        }
+    }
+}
+
+/// Client address a single trusted proxy saw, the rightmost `X-Forwarded-For`
+/// entry. Entries before it come from the client and can be made up, e.g. to
+/// dodge the lockout by IP.
+/// # Example usage
+/// ```rust
+/// use rush::types::login_attempt::forwarded_client;
+///
+/// assert_eq!(forwarded_client("1.2.3.4, 203.0.113.7"), Some("203.0.113.7".parse().unwrap()));
+/// assert_eq!(forwarded_client("2001:db8::1"), Some("2001:db8::1".parse().unwrap()));
+/// assert_eq!(forwarded_client("203.0.113.7, unknown"), None);
+/// ```
+pub fn forwarded_client(x_forwarded_for: &str) -> Option<IpAddr> {
+    x_forwarded_for.rsplit(',').next().and_then(|ip| ip.trim().parse().ok())
+}
+
+/// Failed logins of one key, like the `login_failures` table
+#[derive(Debug, Clone)]
+pub struct LoginFailures {
+    pub key: String,
+    /// Failures since the window started, see [`LockoutPolicy`]
+    pub failures: i32,
+    pub last_failure_on: NaiveDateTime,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+/// How many failures within `window_minutes` lock a key, and for how long
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    pub max_failures: i32,
+    pub window_minutes: i64,
+    pub lockout_minutes: i64,
+}
+
+/// Locks out a single account quickly
+pub const EMAIL_LOCKOUT: LockoutPolicy = LockoutPolicy {
+    max_failures: 5,
+    window_minutes: 15,
+    lockout_minutes: 15,
+};
+
+/// Allows more failures, many people can share an address behind a NAT
+pub const IP_LOCKOUT: LockoutPolicy = LockoutPolicy {
+    max_failures: 50,
+    window_minutes: 15,
+    lockout_minutes: 30,
+};
+
+/// Delay before answering a failed login, doubling with every failure up to 8 seconds
+/// # Example usage
+/// ```rust
+/// use std::time::Duration;
+/// use rush::types::login_attempt::failure_delay;
+///
+/// assert_eq!(failure_delay(1), Duration::from_millis(250));
+/// assert_eq!(failure_delay(3), Duration::from_secs(1));
+/// assert_eq!(failure_delay(40), Duration::from_secs(8));
+/// ```
+pub fn failure_delay(failures: i32) -> Duration {
+    let exponent = (failures.max(1) - 1).min(5) as u32;
+    Duration::from_millis(250 * 2u64.pow(exponent)).min(Duration::from_secs(8))
+}
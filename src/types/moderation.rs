@@ -0,0 +1,102 @@
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::types::account::{AccountId, Role, Session};
+
+/// Who is changing a question or answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Actor {
+    /// The account that wrote it
+    Author(AccountId),
+    /// A moderator changing someone else's content, which gets recorded
+    Moderator(AccountId),
+}
+
+impl Actor {
+    /// Decide in which capacity `session` may change a resource, given whether it owns it.
+    /// Acting as a moderator takes a login that passed two-factor authentication.
+    /// # Example usage
+    /// ```rust
+    /// use chrono::Utc;
+    /// use rush::types::account::{AccountId, Role, Session};
+    /// use rush::types::moderation::Actor;
+    ///
+    /// let session = Session { exp: Utc::now(), nbf: Utc::now(), account_id: AccountId(1), session_id: 1, role: Role::User, mfa: false, api_key: None };
+    /// assert_eq!(Actor::resolve(&session, true).unwrap(), Actor::Author(AccountId(1)));
+    /// assert!(Actor::resolve(&session, false).is_err());
+    ///
+    /// let session = Session { role: Role::Moderator, ..session };
+    /// assert!(Actor::resolve(&session, false).is_err());
+    ///
+    /// let session = Session { mfa: true, ..session };
+    /// assert_eq!(Actor::resolve(&session, false).unwrap(), Actor::Moderator(AccountId(1)));
+    /// ```
+    pub fn resolve(session: &Session, is_owner: bool) -> Result<Actor, Error> {
+        if is_owner {
+            Ok(Actor::Author(session.account_id.clone()))
+        } else if session.role >= Role::Moderator && session.mfa {
+            Ok(Actor::Moderator(session.account_id.clone()))
+        } else if session.role >= Role::Moderator {
+            Err(Error::SecondFactorRequired)
+        } else {
+            Err(Error::Unauthorized)
+        }
+    }
+
+    pub fn account_id(&self) -> &AccountId {
+        match self {
+            Actor::Author(account_id) | Actor::Moderator(account_id) => account_id,
+        }
+    }
+}
+
+/// What a moderator did
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+    UpdateQuestion,
+    DeleteQuestion,
+    UpdateAnswer,
+    DeleteAnswer,
+}
+
+impl ModerationAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationAction::UpdateQuestion => "update_question",
+            ModerationAction::DeleteQuestion => "delete_question",
+            ModerationAction::UpdateAnswer => "update_answer",
+            ModerationAction::DeleteAnswer => "delete_answer",
+        }
+    }
+}
+
+impl FromStr for ModerationAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "update_question" => Ok(ModerationAction::UpdateQuestion),
+            "delete_question" => Ok(ModerationAction::DeleteQuestion),
+            "update_answer" => Ok(ModerationAction::UpdateAnswer),
+            "delete_answer" => Ok(ModerationAction::DeleteAnswer),
+            other => Err(Error::InvalidParameter(format!("action={}", other))),
+        }
+    }
+}
+
+/// Entry of the moderation log, kept after the content itself is gone
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ModerationEntry {
+    pub id: i32,
+    pub moderator_id: AccountId,
+    pub action: ModerationAction,
+    /// Id of the question or answer, depending on the action
+    pub target_id: i32,
+    /// Author of the content the moderator changed
+    pub author_id: Option<AccountId>,
+    pub created_on: NaiveDateTime,
+}
@@ -0,0 +1,75 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Login started at `/oidc/login`, waiting for the identity provider to
+/// redirect back. Like the `oidc_logins` table.
+#[derive(Debug, Clone)]
+pub struct OidcLogin {
+    /// See [`crate::types::token::hash_token`], the state itself only travels through the browser
+    pub state_hash: String,
+    /// PKCE secret, sent to the provider only when trading the code
+    pub code_verifier: String,
+    /// Has to come back in the ID token, so a token can't be replayed into another login
+    pub nonce: String,
+    pub expires_on: NaiveDateTime,
+}
+
+/// Query of the redirect back from the identity provider, which sends
+/// `error` instead of `code` when the user didn't sign in
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct OidcCallback {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Parts of `/.well-known/openid-configuration` the login needs
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// Response of the token endpoint, rush only needs the ID token
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ProviderTokens {
+    pub id_token: String,
+}
+
+/// Claims of the ID token besides the ones the signature check covers
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub nonce: Option<String>,
+    /// How the user signed in, providers send `mfa` after a second factor
+    #[serde(default)]
+    pub amr: Vec<String>,
+}
+
+impl IdTokenClaims {
+    pub fn passed_mfa(&self) -> bool {
+        self.amr.iter().any(|method| method == "mfa")
+    }
+}
+
+/// S256 code challenge of RFC 7636 for a verifier
+/// # Example usage
+/// ```rust
+/// use rush::types::oidc::pkce_challenge;
+///
+/// assert_eq!(
+///     pkce_challenge("M25iVXpKU3puUjFaYWg3T1NDTDQtcW1ROUY5YXlwalNoc0hhakxifmZHag"),
+///     "qjrzSW9gMiUgpUvqgEPE4_-8swvyCtfOVvg55o5S_es"
+/// );
+/// ```
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
@@ -1,55 +1,262 @@
 use std::collections::HashMap;
 
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
 use crate::errors::Error;
 
+/// Page size used when the client doesn't send a `limit`
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+/// Largest `limit` a client may ask for
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Page size settings, taken from the config
+#[derive(Debug, Clone, Copy)]
+pub struct PageSize {
+    pub default: i64,
+    pub max: i64,
+}
+
+impl Default for PageSize {
+    fn default() -> Self {
+        PageSize {
+            default: DEFAULT_PAGE_SIZE,
+            max: MAX_PAGE_SIZE,
+        }
+    }
+}
+
+/// Position in a listing, handed to clients as an opaque string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cursor {
+    /// Items following the one with this `(created_on, id)`
+    After(NaiveDateTime, i32),
+    /// Items preceding the one with this `(created_on, id)`
+    Before(NaiveDateTime, i32),
+    /// Items from this offset on, for orderings that have no keyset
+    Offset(i64),
+}
+
+impl Cursor {
+    /// Encode the cursor into the opaque string used in `next`/`prev`
+    /// # Example usage
+    /// ```rust
+    /// use rush::types::pagination::Cursor;
+    ///
+    /// let cursor = Cursor::Offset(40);
+    /// assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+    /// ```
+    pub fn encode(&self) -> String {
+        let raw = match self {
+            Cursor::After(created_on, id) => format!("a.{}.{}", created_on.and_utc().timestamp_micros(), id),
+            Cursor::Before(created_on, id) => format!("b.{}.{}", created_on.and_utc().timestamp_micros(), id),
+            Cursor::Offset(offset) => format!("o.{}", offset),
+        };
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    /// Decode a cursor previously produced by [`Cursor::encode`]
+    /// # Example usage
+    /// ```rust
+    /// use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    /// use base64::Engine;
+    /// use rush::types::pagination::Cursor;
+    ///
+    /// assert_eq!(Cursor::decode(&URL_SAFE_NO_PAD.encode("o.20")).unwrap(), Cursor::Offset(20));
+    /// assert!(Cursor::decode(&URL_SAFE_NO_PAD.encode("o.-20")).is_err());
+    /// ```
+    pub fn decode(cursor: &str) -> Result<Cursor, Error> {
+        let invalid = || Error::InvalidParameter("cursor".to_string());
+        let raw = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+
+        let parts: Vec<&str> = raw.split('.').collect();
+        let keyset = |micros: &str, id: &str| -> Result<(NaiveDateTime, i32), Error> {
+            let micros = micros.parse::<i64>().map_err(|_| invalid())?;
+            let created_on = NaiveDateTime::from_timestamp_micros(micros).ok_or_else(invalid)?;
+            Ok((created_on, id.parse::<i32>().map_err(|_| invalid())?))
+        };
+        match parts.as_slice() {
+            ["a", micros, id] => keyset(micros, id).map(|(c, i)| Cursor::After(c, i)),
+            ["b", micros, id] => keyset(micros, id).map(|(c, i)| Cursor::Before(c, i)),
+            ["o", offset] => match offset.parse::<i64>() {
+                // Postgres rejects a negative OFFSET, so it's no position in any listing
+                Ok(offset) if offset >= 0 => Ok(Cursor::Offset(offset)),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
 /// Pagination struct which is getting extract
 /// from query params
-#[derive(Default, Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Pagination {
-    /// The index of the last item which has to be returned
-    pub limit: Option<i32>,
-    /// The index of the first item which has to be returned
-    //TODO: Why i32?
-    pub offset: i32,
+    /// The maximum number of items which have to be returned
+    pub limit: i64,
+    /// Where the page starts, `None` for the first page
+    pub cursor: Option<Cursor>,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Pagination {
+            limit: DEFAULT_PAGE_SIZE,
+            cursor: None,
+        }
+    }
 }
 
 /// Extract query parameters from the `/questions` route
 /// # Example query
 /// GET requests to this route can have a pagination attached so we just
 /// return the questions we need
-/// `/questions?start=1&end=10`
+/// `/questions?limit=10&cursor=YS4xNzE...` or `/questions?limit=10&offset=20`
 /// # Example usage
 /// ```rust
 /// use std::collections::HashMap;
-/// use rush::types::pagination;
+/// use rush::types::pagination::{self, Cursor, PageSize};
 ///
 /// let mut query = HashMap::new();
 /// query.insert("limit".to_string(), "1".to_string());
 /// query.insert("offset".to_string(), "10".to_string());
-/// let p = pagination::extract_pagination(query).unwrap();
-/// assert_eq!(p.limit, Some(1));
-/// assert_eq!(p.offset, 10);
+/// let p = pagination::extract_pagination(&query, PageSize::default()).unwrap();
+/// assert_eq!(p.limit, 1);
+/// assert_eq!(p.cursor, Some(Cursor::Offset(10)));
 /// ```
-pub fn extract_pagination(params: HashMap<String, String>) -> Result<Pagination, Error> {
-    // Could be improved in the future
-    if params.contains_key("limit") && params.contains_key("offset") {
-        return Ok(Pagination {
-            // Takes the "limit" parameter in the query and tries to convert it to a number
-            limit: Some(
-                params
-                    .get("limit")
-                    .unwrap()
-                    .parse()
-                    .map_err(Error::ParseError)?,
-            ),
-            // Takes the "offset" parameter in the query and tries to convert it to a number
-            offset: params
-                .get("offset")
-                .unwrap()
-                .parse()
-                .map_err(Error::ParseError)?,
-        });
+pub fn extract_pagination(params: &HashMap<String, String>, page_size: PageSize) -> Result<Pagination, Error> {
+    // Takes the "limit" parameter in the query and tries to convert it to a number
+    let limit = match params.get("limit") {
+        Some(limit) => limit.parse::<i64>().map_err(Error::ParseError)?,
+        None => page_size.default,
+    };
+
+    let cursor = match (params.get("cursor"), params.get("offset")) {
+        (Some(_), Some(_)) => {
+            return Err(Error::InvalidParameter("cursor and offset are exclusive".to_string()))
+        }
+        (Some(cursor), None) => Some(Cursor::decode(cursor)?),
+        // Takes the "offset" parameter in the query and tries to convert it to a number
+        (None, Some(offset)) => Some(Cursor::Offset(offset.parse::<i64>().map_err(Error::ParseError)?.max(0))),
+        (None, None) => None,
+    };
+
+    Ok(Pagination {
+        limit: limit.clamp(1, page_size.max.max(1)),
+        cursor,
+    })
+}
+
+/// One page of a listing, together with the cursors of its neighbours
+#[derive(Serialize, Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of items in the whole listing
+    pub total: i64,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Build a page from items fetched with `LIMIT limit + 1`, in display order.
+    /// The extra item only tells whether there is more in the direction of the
+    /// cursor, so it is dropped. `keyset` says whether the ordering follows
+    /// `(created_on, id)`; other orderings page with offset cursors.
+    pub fn from_fetched<F>(
+        mut items: Vec<T>,
+        total: i64,
+        pagination: &Pagination,
+        keyset: bool,
+        key: F,
+    ) -> Page<T>
+    where
+        F: Fn(&T) -> (NaiveDateTime, i32),
+    {
+        let limit = pagination.limit as usize;
+        let has_more = items.len() > limit;
+        let after = |item: &T| {
+            let (created_on, id) = key(item);
+            Cursor::After(created_on, id).encode()
+        };
+        let before = |item: &T| {
+            let (created_on, id) = key(item);
+            Cursor::Before(created_on, id).encode()
+        };
+
+        let (next, prev) = match (&pagination.cursor, keyset) {
+            (Some(Cursor::Before(..)), true) => {
+                if has_more {
+                    items.remove(0);
+                }
+                let prev = if has_more { items.first().map(before) } else { None };
+                (items.last().map(after), prev)
+            }
+            (cursor, true) => {
+                items.truncate(limit);
+                let next = if has_more { items.last().map(after) } else { None };
+                let prev = if cursor.is_some() { items.first().map(before) } else { None };
+                (next, prev)
+            }
+            (cursor, false) => {
+                items.truncate(limit);
+                let offset = match cursor {
+                    Some(Cursor::Offset(offset)) => *offset,
+                    _ => 0,
+                };
+                let next = has_more.then(|| Cursor::Offset(offset + pagination.limit).encode());
+                let prev = (offset > 0).then(|| Cursor::Offset((offset - pagination.limit).max(0)).encode());
+                (next, prev)
+            }
+        };
+
+        Page {
+            items,
+            total,
+            next,
+            prev,
+        }
     }
 
-    Err(Error::MissingParameters)
+    /// RFC 8288 `Link` header pointing at the neighbouring pages, keeping
+    /// every other query parameter of the request
+    /// # Example usage
+    /// ```rust
+    /// use rush::types::pagination::Page;
+    ///
+    /// let page: Page<i32> = Page { items: vec![], total: 0, next: Some("abc".to_string()), prev: None };
+    /// let query = vec![("limit".to_string(), "5".to_string()), ("cursor".to_string(), "xyz".to_string())];
+    /// assert_eq!(
+    ///     page.link_header("/questions", &query).unwrap(),
+    ///     r#"</questions?limit=5&cursor=abc>; rel="next""#
+    /// );
+    /// ```
+    pub fn link_header(&self, path: &str, query: &[(String, String)]) -> Option<String> {
+        let link = |cursor: &String, rel: &str| {
+            let mut params: Vec<(&str, &str)> = query
+                .iter()
+                .filter(|(key, _)| key != "cursor" && key != "offset")
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+            params.push(("cursor", cursor));
+            let query = serde_urlencoded::to_string(params).unwrap_or_default();
+            format!("<{}?{}>; rel=\"{}\"", path, query, rel)
+        };
+
+        let links: Vec<String> = [
+            self.next.as_ref().map(|cursor| link(cursor, "next")),
+            self.prev.as_ref().map(|cursor| link(cursor, "prev")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if links.is_empty() {
+            None
+        } else {
+            Some(links.join(", "))
+        }
+    }
 }
@@ -0,0 +1,317 @@
+use std::cmp::Reverse;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::types::answer::Answer;
+use crate::types::question::{Question, QuestionId};
+use crate::types::search::tokenize;
+
+/// Related questions from the knowledge base a prompt may cite at most
+pub const MAX_SOURCES: usize = 3;
+/// Longest [`PromptTemplate::version`], like the `answers.prompt_version` column
+pub const MAX_PROMPT_VERSION_LENGTH: usize = 64;
+/// Words of a question [`retrieval_terms`] returns at most
+const MAX_RETRIEVAL_TERMS: usize = 12;
+/// Too common to tell questions apart
+const STOP_WORDS: [&str; 20] = [
+    "the", "and", "for", "with", "how", "does", "what", "why", "when", "this", "that", "from", "are", "can",
+    "not", "have", "you", "your", "but", "there",
+];
+
+/// What the AI provider is asked, see [`PromptTemplate::render`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    /// Instructions the model follows for every question
+    pub system: String,
+    pub user: String,
+    /// Questions of the knowledge base that made it into the prompt
+    pub sources: Vec<QuestionId>,
+}
+
+/// Answered question similar to the one being answered, given to the model
+/// as context it can cite
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnowledgeSource {
+    pub question_id: QuestionId,
+    pub title: String,
+    /// The accepted answer, or the best scored one people wrote
+    pub answer: String,
+}
+
+/// How generated answers are asked for, set with `PROMPT_TEMPLATE_FILE`.
+/// `user` may contain the placeholders `{title}`, `{tags}`, `{content}`,
+/// `{sources}` and `{answers}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    /// Stored with every answer the template generated, so change it along with the template
+    pub version: String,
+    pub system: String,
+    pub user: String,
+    /// Estimated tokens the whole prompt may take, see [`estimate_tokens`]
+    pub token_budget: usize,
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        PromptTemplate {
+            version: "default-v2".to_string(),
+            system: "You answer questions on a support Q&A site. Answer in the language of the question, \
+                be concise and accurate, and say so when you are unsure. Base the answer on the related \
+                questions from the knowledge base where they apply and cite them like [#12], don't invent \
+                product details they don't mention. Existing answers may be wrong or incomplete, improve on \
+                them instead of repeating them."
+                .to_string(),
+            user: "Title: {title}\nTags: {tags}\n\n{content}\n\nRelated questions from the knowledge base:\n{sources}\n\
+                Existing answers:\n{answers}"
+                .to_string(),
+            token_budget: 3000,
+        }
+    }
+}
+
+impl PromptTemplate {
+    pub fn from_file(path: &str) -> Result<PromptTemplate, Error> {
+        let file = std::fs::read_to_string(path)
+            .map_err(|e| Error::AiConfigError(format!("cannot read {}: {}", path, e)))?;
+        let template: PromptTemplate = serde_json::from_str(&file)
+            .map_err(|e| Error::AiConfigError(format!("cannot parse {}: {}", path, e)))?;
+        if template.version.is_empty() {
+            return Err(Error::AiConfigError(format!("{} has no version", path)));
+        }
+        // Checked here, Postgres would only reject it with the first generated answer
+        if template.version.chars().count() > MAX_PROMPT_VERSION_LENGTH {
+            return Err(Error::AiConfigError(format!(
+                "version in {} is longer than {} characters",
+                path, MAX_PROMPT_VERSION_LENGTH
+            )));
+        }
+        Ok(template)
+    }
+
+    /// Fills in the question, then as many knowledge base sources and answers
+    /// of the question as the token budget allows, best first. When even the
+    /// question doesn't fit, its body is cut short, and when none of the
+    /// answers fit, `{answers}` says how many were left out.
+    /// # Example usage
+    /// ```rust
+    /// use rush::types::answer::{Answer, AnswerId};
+    /// use rush::types::prompt::{KnowledgeSource, PromptTemplate};
+    /// use rush::types::question::{Question, QuestionId};
+    ///
+    /// let question = Question {
+    ///     id: QuestionId(1),
+    ///     title: "Borrowing".to_string(),
+    ///     content: "Why does this not compile?".to_string(),
+    ///     tags: Some(vec!["rust".to_string()]),
+    ///     account_id: None,
+    ///     created_on: None,
+    ///     score: 0,
+    ///     status: Default::default(),
+    ///     accepted_answer_id: None,
+    /// };
+    /// let answer = |id, score, content: &str| Answer {
+    ///     id: AnswerId(id),
+    ///     content: content.to_string(),
+    ///     question_id: QuestionId(1),
+    ///     account_id: None,
+    ///     created_on: None,
+    ///     score,
+    ///     prompt_version: None,
+    /// };
+    /// let answers = [answer(1, 0, "Clone it."), answer(2, 3, "Borrow it instead.")];
+    /// let mut template = PromptTemplate {
+    ///     version: "test".to_string(),
+    ///     system: "Be brief.".to_string(),
+    ///     user: "{title} [{tags}]\n{content}\n{answers}".to_string(),
+    ///     token_budget: 100,
+    /// };
+    ///
+    /// let prompt = template.render(&question, &answers, &[]);
+    /// assert_eq!(prompt.system, "Be brief.");
+    /// assert_eq!(
+    ///     prompt.user,
+    ///     "Borrowing [rust]\nWhy does this not compile?\nAnswer (score 3):\nBorrow it instead.\n\nAnswer (score 0):\nClone it.\n"
+    /// );
+    ///
+    /// template.token_budget = 25;
+    /// let prompt = template.render(&question, &answers, &[]);
+    /// assert_eq!(prompt.user, "Borrowing [rust]\nWhy does this not compile?\nAnswer (score 3):\nBorrow it instead.\n");
+    ///
+    /// template.token_budget = 18;
+    /// let prompt = template.render(&question, &answers, &[]);
+    /// assert_eq!(
+    ///     prompt.user,
+    ///     "Borrowing [rust]\nWhy does this not compile?\n(2 existing answers omitted for length)"
+    /// );
+    ///
+    /// template.token_budget = 12;
+    /// let prompt = template.render(&question, &answers[..1], &[]);
+    /// assert_eq!(prompt.user, "Borrowing [rust]\nWhy does this n…\n(1 existing answer omitted for length)");
+    ///
+    /// let source = KnowledgeSource {
+    ///     question_id: QuestionId(7),
+    ///     title: "Moved value".to_string(),
+    ///     answer: "Borrow with &.".to_string(),
+    /// };
+    /// template.user = "{content}\n{sources}".to_string();
+    /// template.token_budget = 100;
+    /// let prompt = template.render(&question, &[], &[source]);
+    /// assert_eq!(prompt.user, "Why does this not compile?\n[#7] Moved value\nBorrow with &.\n");
+    /// assert_eq!(prompt.sources, vec![QuestionId(7)]);
+    /// ```
+    pub fn render(&self, question: &Question, answers: &[Answer], sources: &[KnowledgeSource]) -> Prompt {
+        let tags = question
+            .tags
+            .as_ref()
+            .filter(|tags| !tags.is_empty())
+            .map(|tags| tags.join(", "))
+            .unwrap_or("none".to_string());
+        let frame = fill(
+            &self.user,
+            &[("title", &question.title), ("tags", &tags), ("content", ""), ("sources", ""), ("answers", "")],
+        );
+        let mut budget = self
+            .token_budget
+            .saturating_sub(estimate_tokens(&self.system) + estimate_tokens(&frame));
+
+        let content = truncate_to_tokens(question.content.trim(), budget);
+        budget = budget.saturating_sub(estimate_tokens(&content));
+
+        // Templates without the placeholder get no sources, so none can be cited
+        let mut cited = Vec::new();
+        let mut included = Vec::new();
+        if self.user.contains("{sources}") {
+            for source in sources {
+                let text = format!("[#{}] {}\n{}\n", source.question_id.0, source.title, source.answer.trim());
+                let cost = estimate_tokens(&text);
+                if cost <= budget {
+                    budget -= cost;
+                    cited.push(source.question_id);
+                    included.push(text);
+                }
+            }
+        }
+        let sources = if included.is_empty() {
+            "None found.\n".to_string()
+        } else {
+            included.join("\n")
+        };
+
+        // Stable, so equally scored answers stay oldest first
+        let mut ranked: Vec<&Answer> = answers.iter().collect();
+        ranked.sort_by_key(|answer| Reverse(answer.score));
+        let mut included = Vec::new();
+        for answer in ranked {
+            let text = format!("Answer (score {}):\n{}\n", answer.score, answer.content.trim());
+            let cost = estimate_tokens(&text);
+            if cost <= budget {
+                budget -= cost;
+                included.push(text);
+            }
+        }
+        let answers = match (answers.len(), included.is_empty()) {
+            (0, _) => "None yet.".to_string(),
+            // Left empty, the model would take the question for unanswered
+            (1, true) => "(1 existing answer omitted for length)".to_string(),
+            (omitted, true) => format!("({} existing answers omitted for length)", omitted),
+            (_, false) => included.join("\n"),
+        };
+
+        Prompt {
+            system: self.system.clone(),
+            user: fill(
+                &self.user,
+                &[
+                    ("title", &question.title),
+                    ("tags", &tags),
+                    ("content", &content),
+                    ("sources", &sources),
+                    ("answers", &answers),
+                ],
+            ),
+            sources: cited,
+        }
+    }
+}
+
+/// Distinctive words of the question, to search for questions like it with
+/// any of them
+/// # Example usage
+/// ```rust
+/// use rush::types::prompt::retrieval_terms;
+/// use rush::types::question::{Question, QuestionId};
+///
+/// let question = Question {
+///     id: QuestionId(1),
+///     title: "How does login work?".to_string(),
+///     content: "Login fails with a 401 after a password reset".to_string(),
+///     tags: Some(vec!["auth".to_string()]),
+///     account_id: None,
+///     created_on: None,
+///     score: 0,
+///     status: Default::default(),
+///     accepted_answer_id: None,
+/// };
+/// assert_eq!(
+///     retrieval_terms(&question),
+///     vec!["login", "work", "auth", "fails", "401", "after", "password", "reset"]
+/// );
+/// ```
+pub fn retrieval_terms(question: &Question) -> Vec<String> {
+    let tags = question.tags.as_deref().unwrap_or_default().join(" ");
+    let mut terms: Vec<String> = Vec::new();
+    for word in tokenize(&format!("{} {} {}", question.title, tags, question.content)) {
+        if word.chars().count() >= 3 && !STOP_WORDS.contains(&word.as_str()) && !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms.truncate(MAX_RETRIEVAL_TERMS);
+    terms
+}
+
+/// Rough token count of English text, about four characters per token
+/// # Example usage
+/// ```rust
+/// use rush::types::prompt::estimate_tokens;
+///
+/// assert_eq!(estimate_tokens(""), 0);
+/// assert_eq!(estimate_tokens("Hello world"), 3);
+/// ```
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+fn truncate_to_tokens(text: &str, tokens: usize) -> String {
+    if estimate_tokens(text) <= tokens {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take((tokens * 4).saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Replaces the placeholders in one pass, so braces in the values stay as they are
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let tail = &rest[start + 1..];
+        match values
+            .iter()
+            .find(|(name, _)| tail.starts_with(name) && tail[name.len()..].starts_with('}'))
+        {
+            Some((name, value)) => {
+                filled.push_str(value);
+                rest = &tail[name.len() + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = tail;
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
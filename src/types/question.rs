@@ -1,11 +1,36 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 
+use crate::errors::Error;
+use crate::types::account::AccountId;
+use crate::types::answer::{Answer, AnswerId};
+use crate::types::pagination::{extract_pagination, PageSize, Pagination};
+use crate::types::tag::{extract_tag_filter, TagFilter, TagMatch};
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Question {
     pub id: QuestionId,
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    /// The author, set by the repository and ignored when sent by a client
+    #[serde(default)]
+    pub account_id: Option<AccountId>,
+    /// Set by the repository and ignored when sent by a client
+    #[serde(default)]
+    pub created_on: Option<NaiveDateTime>,
+    /// Sum of all up (+1) and down (-1) votes, maintained by the repository
+    #[serde(default)]
+    pub score: i32,
+    /// Maintained by the repository, see [`QuestionStatus`]
+    #[serde(default)]
+    pub status: QuestionStatus,
+    /// The answer the author accepted, which makes the question resolved
+    #[serde(default)]
+    pub accepted_answer_id: Option<AnswerId>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash, Copy)]
@@ -17,3 +42,251 @@ pub struct NewQuestion {
     pub content: String,
     pub tags: Option<Vec<String>>,
 }
+
+/// Lifecycle of a question. `answered` is set by the first answer and
+/// `resolved` by accepting one, while `closed` is set by the author.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestionStatus {
+    #[default]
+    Open,
+    Answered,
+    Resolved,
+    Closed,
+}
+
+impl QuestionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuestionStatus::Open => "open",
+            QuestionStatus::Answered => "answered",
+            QuestionStatus::Resolved => "resolved",
+            QuestionStatus::Closed => "closed",
+        }
+    }
+}
+
+impl FromStr for QuestionStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(QuestionStatus::Open),
+            "answered" => Ok(QuestionStatus::Answered),
+            "resolved" => Ok(QuestionStatus::Resolved),
+            "closed" => Ok(QuestionStatus::Closed),
+            other => Err(Error::InvalidParameter(format!("status={}", other))),
+        }
+    }
+}
+
+/// Body of `PUT /questions/{id}/status`. Only `open` and `closed` can be
+/// requested, reopening restores `answered`/`resolved` where they apply.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct StatusUpdate {
+    pub status: QuestionStatus,
+}
+
+/// A question together with all of its answers, oldest answer first
+#[derive(Serialize, Debug, Clone)]
+pub struct QuestionWithAnswers {
+    #[serde(flatten)]
+    pub question: Question,
+    pub answers: Vec<Answer>,
+}
+
+/// Order in which `/questions` returns its results
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestionSort {
+    /// Newest question first
+    Newest,
+    /// Oldest question first
+    #[default]
+    Oldest,
+    /// Highest score first, ties broken by age
+    Score,
+    /// Most recently active first, going by the newest answer or the question itself
+    Activity,
+    /// Questions with the fewest answers first, ties broken by age
+    Unanswered,
+}
+
+impl QuestionSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuestionSort::Newest => "newest",
+            QuestionSort::Oldest => "oldest",
+            QuestionSort::Score => "score",
+            QuestionSort::Activity => "activity",
+            QuestionSort::Unanswered => "unanswered",
+        }
+    }
+
+    /// Whether the ordering follows `(created_on, id)`, which allows keyset cursors
+    pub fn is_keyset(&self) -> bool {
+        matches!(self, QuestionSort::Oldest | QuestionSort::Newest)
+    }
+}
+
+/// Extract the `sort` query parameter from the `/questions` route
+/// # Example usage
+/// ```rust
+/// use std::collections::HashMap;
+/// use rush::types::question::{extract_sort, QuestionSort};
+///
+/// let mut query = HashMap::new();
+/// assert_eq!(extract_sort(&query).unwrap(), QuestionSort::Oldest);
+/// query.insert("sort".to_string(), "score".to_string());
+/// assert_eq!(extract_sort(&query).unwrap(), QuestionSort::Score);
+/// ```
+pub fn extract_sort(params: &HashMap<String, String>) -> Result<QuestionSort, Error> {
+    match params.get("sort").map(String::as_str) {
+        None | Some("oldest") => Ok(QuestionSort::Oldest),
+        Some("newest") => Ok(QuestionSort::Newest),
+        Some("score") => Ok(QuestionSort::Score),
+        Some("activity") => Ok(QuestionSort::Activity),
+        Some("unanswered") => Ok(QuestionSort::Unanswered),
+        Some(other) => Err(Error::InvalidParameter(format!("sort={}", other))),
+    }
+}
+
+/// Extract the optional `status` filter from the `/questions` route
+/// # Example usage
+/// ```rust
+/// use std::collections::HashMap;
+/// use rush::types::question::{extract_status, QuestionStatus};
+///
+/// let mut query = HashMap::new();
+/// assert_eq!(extract_status(&query).unwrap(), None);
+/// query.insert("status".to_string(), "open".to_string());
+/// assert_eq!(extract_status(&query).unwrap(), Some(QuestionStatus::Open));
+/// ```
+pub fn extract_status(params: &HashMap<String, String>) -> Result<Option<QuestionStatus>, Error> {
+    params.get("status").map(|s| s.parse()).transpose()
+}
+
+/// Parse a `created_after`/`created_before` value, either RFC 3339 or a plain
+/// date which stands for midnight UTC
+fn parse_date(key: &str, value: &str) -> Result<NaiveDateTime, Error> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.naive_utc());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .ok_or_else(|| Error::InvalidParameter(format!("{}={}", key, value)))
+}
+
+/// Everything the `/questions` listing can be narrowed and ordered by
+#[derive(Debug, Clone, Default)]
+pub struct QuestionQuery {
+    pub pagination: Pagination,
+    pub sort: QuestionSort,
+    pub status: Option<QuestionStatus>,
+    pub tags: Option<TagFilter>,
+    /// Only questions asked by this account
+    pub author: Option<AccountId>,
+    /// Only questions created strictly after this moment
+    pub created_after: Option<NaiveDateTime>,
+    /// Only questions created strictly before this moment
+    pub created_before: Option<NaiveDateTime>,
+    /// Only questions with (`true`) or without (`false`) any answer
+    pub has_answers: Option<bool>,
+}
+
+impl QuestionQuery {
+    /// Whether a question passes every filter apart from `has_answers`,
+    /// which needs the answers and is left to the caller
+    pub fn matches(&self, question: &Question) -> bool {
+        let created_on = question.created_on.unwrap_or_default();
+        (self.status.is_none() || self.status == Some(question.status))
+            && (self.author.is_none() || self.author == question.account_id)
+            && self.created_after.iter().all(|after| created_on > *after)
+            && self.created_before.iter().all(|before| created_on < *before)
+            && match &self.tags {
+                Some(filter) => filter.matches(&question.tags),
+                None => true,
+            }
+    }
+
+    /// The query as parameters again, without the cursor, so that links to
+    /// other pages keep the same filters
+    pub fn to_params(&self) -> Vec<(String, String)> {
+        let date = |date: &NaiveDateTime| date.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string();
+        let mut params = vec![
+            ("limit".to_string(), self.pagination.limit.to_string()),
+            ("sort".to_string(), self.sort.as_str().to_string()),
+        ];
+        if let Some(status) = self.status {
+            params.push(("status".to_string(), status.as_str().to_string()));
+        }
+        if let Some(filter) = &self.tags {
+            params.extend(filter.tags.iter().map(|tag| ("tag".to_string(), tag.clone())));
+            if filter.mode == TagMatch::All {
+                params.push(("tag_match".to_string(), "all".to_string()));
+            }
+        }
+        if let Some(author) = &self.author {
+            params.push(("author".to_string(), author.0.to_string()));
+        }
+        if let Some(after) = &self.created_after {
+            params.push(("created_after".to_string(), date(after)));
+        }
+        if let Some(before) = &self.created_before {
+            params.push(("created_before".to_string(), date(before)));
+        }
+        if let Some(has_answers) = self.has_answers {
+            params.push(("has_answers".to_string(), has_answers.to_string()));
+        }
+        params
+    }
+}
+
+/// Extract every parameter of the `/questions` route
+/// # Example query
+/// `/questions?sort=newest&has_answers=false&created_before=2026-10-17T09:00:00Z`
+/// # Example usage
+/// ```rust
+/// use rush::types::pagination::PageSize;
+/// use rush::types::question::{extract_question_query, QuestionSort};
+///
+/// let query = vec![
+///     ("sort".to_string(), "unanswered".to_string()),
+///     ("author".to_string(), "7".to_string()),
+///     ("created_before".to_string(), "2026-10-17".to_string()),
+///     ("has_answers".to_string(), "false".to_string()),
+/// ];
+/// let query = extract_question_query(&query, PageSize::default()).unwrap();
+/// assert_eq!(query.sort, QuestionSort::Unanswered);
+/// assert_eq!(query.author.unwrap().0, 7);
+/// assert_eq!(query.created_before.unwrap().to_string(), "2026-10-17 00:00:00");
+/// assert_eq!(query.has_answers, Some(false));
+/// ```
+pub fn extract_question_query(params: &[(String, String)], page_size: PageSize) -> Result<QuestionQuery, Error> {
+    // `tag` may be repeated, so it is read from the raw pairs
+    let tags = extract_tag_filter(params)?;
+    let params: HashMap<String, String> = params.iter().cloned().collect();
+
+    let author = match params.get("author") {
+        Some(author) => Some(AccountId(author.parse::<i32>().map_err(Error::ParseError)?)),
+        None => None,
+    };
+    let has_answers = match params.get("has_answers").map(String::as_str) {
+        None => None,
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        Some(other) => return Err(Error::InvalidParameter(format!("has_answers={}", other))),
+    };
+
+    Ok(QuestionQuery {
+        pagination: extract_pagination(&params, page_size)?,
+        sort: extract_sort(&params)?,
+        status: extract_status(&params)?,
+        tags,
+        author,
+        created_after: params.get("created_after").map(|v| parse_date("created_after", v)).transpose()?,
+        created_before: params.get("created_before").map(|v| parse_date("created_before", v)).transpose()?,
+        has_answers,
+    })
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+
+/// Number of results `/search` returns when no `limit` is given
+pub const DEFAULT_SEARCH_LIMIT: i64 = 20;
+/// Upper bound for the `limit` parameter of `/search`
+pub const MAX_SEARCH_LIMIT: i64 = 100;
+
+/// A question or answer matching a search, best match first
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SearchResult {
+    pub question_id: QuestionId,
+    /// Set when the match is in an answer rather than the question itself
+    pub answer_id: Option<AnswerId>,
+    /// Title of the question, also for answer matches
+    pub title: String,
+    /// Excerpt of the matching text with the hits wrapped in `<mark>` tags
+    pub snippet: String,
+    pub rank: f32,
+}
+
+/// Query parameters of the `/search` route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: i64,
+}
+
+/// Extract the query parameters from the `/search` route
+/// # Example query
+/// `/search?q=how+does+login+work&limit=5`
+/// # Example usage
+/// ```rust
+/// use std::collections::HashMap;
+/// use rush::types::search;
+///
+/// let mut query = HashMap::new();
+/// query.insert("q".to_string(), "login".to_string());
+/// let s = search::extract_search(query).unwrap();
+/// assert_eq!(s.q, "login");
+/// assert_eq!(s.limit, search::DEFAULT_SEARCH_LIMIT);
+/// ```
+pub fn extract_search(params: HashMap<String, String>) -> Result<SearchQuery, Error> {
+    let q = match params.get("q") {
+        Some(q) if !q.trim().is_empty() => q.trim().to_owned(),
+        _ => return Err(Error::MissingParameters),
+    };
+
+    let limit = match params.get("limit") {
+        Some(limit) => limit.parse::<i64>().map_err(Error::ParseError)?,
+        None => DEFAULT_SEARCH_LIMIT,
+    };
+
+    Ok(SearchQuery {
+        q,
+        limit: limit.clamp(1, MAX_SEARCH_LIMIT),
+    })
+}
+
+/// Split a text into lowercase alphanumeric words, the poor man's `to_tsvector`
+/// # Example usage
+/// ```rust
+/// use rush::types::search::tokenize;
+///
+/// assert_eq!(tokenize("How does Warp's filter work?"), vec!["how", "does", "warp", "s", "filter", "work"]);
+/// ```
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+
+/// Entry of the `/tags` catalog
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TagCount {
+    pub tag: String,
+    /// Number of questions carrying the tag
+    pub count: i64,
+}
+
+/// How the tags of a [`TagFilter`] are combined
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    /// Questions carrying at least one of the tags
+    #[default]
+    Any,
+    /// Questions carrying every one of the tags
+    All,
+}
+
+/// Tag filter of the `/questions` route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagFilter {
+    pub tags: Vec<String>,
+    pub mode: TagMatch,
+}
+
+impl TagFilter {
+    /// Whether a question with the given tags passes the filter
+    pub fn matches(&self, tags: &Option<Vec<String>>) -> bool {
+        let tags = tags.as_deref().unwrap_or_default();
+        match self.mode {
+            TagMatch::Any => self.tags.iter().any(|t| tags.contains(t)),
+            TagMatch::All => self.tags.iter().all(|t| tags.contains(t)),
+        }
+    }
+}
+
+/// Extract the repeatable `tag` parameter and the `tag_match` mode
+/// # Example query
+/// `/questions?tag=rust&tag=warp&tag_match=all`
+/// # Example usage
+/// ```rust
+/// use rush::types::tag::{extract_tag_filter, TagMatch};
+///
+/// let query = vec![
+///     ("tag".to_string(), "rust".to_string()),
+///     ("tag".to_string(), "warp".to_string()),
+///     ("tag_match".to_string(), "all".to_string()),
+/// ];
+/// let filter = extract_tag_filter(&query).unwrap().unwrap();
+/// assert_eq!(filter.tags, vec!["rust", "warp"]);
+/// assert_eq!(filter.mode, TagMatch::All);
+/// assert!(extract_tag_filter(&[]).unwrap().is_none());
+/// ```
+pub fn extract_tag_filter(params: &[(String, String)]) -> Result<Option<TagFilter>, Error> {
+    let tags: Vec<String> = params
+        .iter()
+        .filter(|(key, _)| key == "tag")
+        .map(|(_, value)| value.to_owned())
+        .collect();
+
+    let mode = match params
+        .iter()
+        .rev()
+        .find(|(key, _)| key == "tag_match")
+        .map(|(_, value)| value.as_str())
+    {
+        None | Some("any") => TagMatch::Any,
+        Some("all") => TagMatch::All,
+        Some(other) => return Err(Error::InvalidParameter(format!("tag_match={}", other))),
+    };
+
+    if tags.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(TagFilter { tags, mode }))
+}
@@ -0,0 +1,161 @@
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::errors::Error;
+use crate::types::account::AccountId;
+use crate::types::totp::SecondFactor;
+
+/// Server-side record of a session. Refreshing swaps the token but keeps the
+/// id, which every access token of the session carries.
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub id: i32,
+    pub account_id: AccountId,
+    /// See [`hash_token`], the token itself is never stored
+    pub token_hash: String,
+    pub expires_on: NaiveDateTime,
+    pub revoked_on: Option<NaiveDateTime>,
+    /// Whether the login passed two-factor authentication, carried over on refresh
+    pub mfa: bool,
+}
+
+impl RefreshToken {
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        self.revoked_on.is_none() && self.expires_on > now
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewRefreshToken {
+    pub account_id: AccountId,
+    pub token_hash: String,
+    pub expires_on: NaiveDateTime,
+    pub mfa: bool,
+}
+
+/// Body of `POST /token/refresh`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Body of `POST /email/verify`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct EmailVerification {
+    pub token: String,
+}
+
+/// Body of `POST /password/reset`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PasswordReset {
+    pub token: String,
+    pub password: String,
+}
+
+/// Returned by `/login` and `/token/refresh`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Lifetime of the access token in seconds
+    pub expires_in: i64,
+}
+
+/// Returned by `/login` instead of tokens when the account has two-factor
+/// authentication, to be completed through `/login/2fa`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LoginChallenge {
+    pub challenge_token: String,
+    /// Lifetime of the challenge in seconds
+    pub expires_in: i64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Tokens(TokenPair),
+    Challenge(LoginChallenge),
+}
+
+/// Body of `POST /login/2fa`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SecondFactorLogin {
+    pub challenge_token: String,
+    #[serde(flatten)]
+    pub factor: SecondFactor,
+}
+
+/// What a single-use token may be used for, most of them are sent by email
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountTokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+    /// Handed out by `/login` between the password and the second factor
+    LoginChallenge,
+}
+
+impl AccountTokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountTokenPurpose::VerifyEmail => "verify_email",
+            AccountTokenPurpose::ResetPassword => "reset_password",
+            AccountTokenPurpose::LoginChallenge => "login_challenge",
+        }
+    }
+}
+
+impl FromStr for AccountTokenPurpose {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "verify_email" => Ok(AccountTokenPurpose::VerifyEmail),
+            "reset_password" => Ok(AccountTokenPurpose::ResetPassword),
+            "login_challenge" => Ok(AccountTokenPurpose::LoginChallenge),
+            other => Err(Error::InvalidParameter(format!("purpose={}", other))),
+        }
+    }
+}
+
+/// Single-use token for the owner of an account, like the `account_tokens` table
+#[derive(Debug, Clone)]
+pub struct AccountToken {
+    pub id: i32,
+    pub account_id: AccountId,
+    pub purpose: AccountTokenPurpose,
+    pub token_hash: String,
+    pub expires_on: NaiveDateTime,
+    pub used_on: Option<NaiveDateTime>,
+}
+
+impl AccountToken {
+    pub fn is_usable(&self, purpose: AccountTokenPurpose, now: NaiveDateTime) -> bool {
+        self.purpose == purpose && self.used_on.is_none() && self.expires_on > now
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAccountToken {
+    pub account_id: AccountId,
+    pub purpose: AccountTokenPurpose,
+    pub token_hash: String,
+    pub expires_on: NaiveDateTime,
+}
+
+/// Refresh tokens and mailed tokens are random, so a plain SHA-256 is enough
+/// to keep them unusable when the table leaks
+/// # Example usage
+/// ```rust
+/// use rush::types::token::hash_token;
+///
+/// let hash = hash_token("token");
+/// assert_eq!(hash.len(), 64);
+/// assert_eq!(hash, hash_token("token"));
+/// ```
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
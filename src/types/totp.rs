@@ -0,0 +1,164 @@
+use chrono::NaiveDateTime;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+
+use crate::types::account::AccountId;
+
+/// Settings every authenticator app understands: SHA-1, 6 digits, 30 seconds
+pub const TOTP_DIGITS: u32 = 6;
+pub const TOTP_PERIOD: i64 = 30;
+/// Steps before and after the current one that are accepted, for clocks that drift
+pub const TOTP_SKEW: i64 = 1;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Second factor of an account, like the `account_totp` table. It only
+/// counts once the account proved its app has the secret.
+#[derive(Debug, Clone)]
+pub struct Totp {
+    pub account_id: AccountId,
+    /// Base32, as shown to the user
+    pub secret: String,
+    pub enabled_on: Option<NaiveDateTime>,
+    /// Newest step a code was accepted for, so a code can't be used twice
+    pub last_used_step: Option<i64>,
+}
+
+impl Totp {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_on.is_some()
+    }
+}
+
+/// Returned by `POST /2fa/enroll`, the URI is usually shown as a QR code
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Body of `POST /2fa/confirm`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TotpConfirmation {
+    pub code: String,
+}
+
+/// Returned once by `POST /2fa/confirm`, only their hashes are stored
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Either a code from the app or one of the recovery codes
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SecondFactor {
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+pub fn generate_secret() -> String {
+    BASE32_NOPAD.encode(&rand::thread_rng().gen::<[u8; 20]>())
+}
+
+/// Codes look like `k3x9-p2mf`, without characters that are easy to mix up
+pub fn generate_recovery_codes() -> Vec<String> {
+    const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = (0..8)
+                .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &code[..4], &code[4..])
+        })
+        .collect()
+}
+
+/// Recovery codes are compared without the dash and case, as people type them
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// HOTP (RFC 4226) code for a time step
+/// # Example usage
+/// ```rust
+/// use rush::types::totp::code_at;
+///
+/// // Test vector of RFC 6238 for SHA-1 at 59 seconds
+/// assert_eq!(code_at(b"12345678901234567890", 59 / 30), "287082");
+/// ```
+pub fn code_at(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC takes keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset], hash[offset + 1], hash[offset + 2], hash[offset + 3]]) & 0x7fff_ffff;
+    format!("{:0width$}", binary % 10u32.pow(TOTP_DIGITS), width = TOTP_DIGITS as usize)
+}
+
+/// Finds the step `code` is valid for at `unix_time`, rejecting steps that were
+/// already used
+/// # Example usage
+/// ```rust
+/// use data_encoding::BASE32_NOPAD;
+/// use rush::types::totp::{code_at, verify_code};
+///
+/// let secret = BASE32_NOPAD.encode(b"12345678901234567890");
+/// let now = 1_700_000_000;
+/// let code = code_at(b"12345678901234567890", now / 30 - 1);
+/// assert_eq!(verify_code(&secret, &code, now, None), Some(now / 30 - 1));
+/// assert_eq!(verify_code(&secret, &code, now, Some(now / 30 - 1)), None);
+/// assert_eq!(verify_code(&secret, &code, now + 90, None), None);
+/// assert_eq!(verify_code(&secret, "12345", now, None), None);
+/// ```
+pub fn verify_code(secret: &str, code: &str, unix_time: i64, last_used_step: Option<i64>) -> Option<i64> {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize {
+        return None;
+    }
+
+    let current = unix_time / TOTP_PERIOD;
+    (current - TOTP_SKEW..=current + TOTP_SKEW)
+        .filter(|step| last_used_step.map(|last| *step > last).unwrap_or(true))
+        .find(|step| code_at(&secret, *step) == code)
+}
+
+/// Key URI for authenticator apps, see
+/// <https://github.com/google/google-authenticator/wiki/Key-Uri-Format>
+/// # Example usage
+/// ```rust
+/// use rush::types::totp::otpauth_uri;
+///
+/// assert_eq!(
+///     otpauth_uri("rush", "jane@example.com", "JBSWY3DPEHPK3PXP"),
+///     "otpauth://totp/rush:jane%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=rush&algorithm=SHA1&digits=6&period=30"
+/// );
+/// ```
+pub fn otpauth_uri(issuer: &str, email: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(email),
+        secret,
+        percent_encode(issuer),
+        TOTP_DIGITS,
+        TOTP_PERIOD
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether a vote raises or lowers the score of a question or answer
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteDirection {
+    Up,
+    Down,
+}
+
+impl VoteDirection {
+    /// The amount this vote contributes to the score
+    /// # Example usage
+    /// ```rust
+    /// use rush::types::vote::VoteDirection;
+    ///
+    /// assert_eq!(VoteDirection::Up.value(), 1);
+    /// assert_eq!(VoteDirection::Down.value(), -1);
+    /// ```
+    pub fn value(&self) -> i32 {
+        match self {
+            VoteDirection::Up => 1,
+            VoteDirection::Down => -1,
+        }
+    }
+}
+
+/// Body of `POST /questions/{id}/votes` and `POST /answers/{id}/votes`,
+/// e.g. `{"direction": "up"}`. Voting again replaces the previous vote.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NewVote {
+    pub direction: VoteDirection,
+}